@@ -0,0 +1,410 @@
+// StaxPing — Unified Network Diagnostics
+// Copyright (c) 2026 StaxDash
+//
+// This source code is provided under the StaxPing Source‑Available License & EULA.
+// You may view, modify, and redistribute this code for personal or internal use.
+// Commercial use of any kind requires explicit written permission from StaxDash.
+//
+// Full license text available in LICENSE and EULA.md.
+
+use clap::{Args, Parser, Subcommand};
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use trust_dns_resolver::proto::rr::RecordType;
+
+use crate::delegation::DelegationOptions;
+use crate::dns::{DnsOptions, IpFamily, Nameserver};
+use crate::mtr::MtrOptions;
+use crate::output::OutputFormat;
+use crate::ping::PingOptions;
+use crate::trace::{TraceOptions, TraceProtocol};
+
+/// CLI argument structure
+#[derive(Parser, Debug)]
+#[command(
+    name = "StaxPing",
+    version = "0.1.0",
+    about = "A clean, unified network diagnostic tool by StaxDash.",
+    long_about = "StaxPing performs DNS lookup, ICMP ping, HTTP checks, and optional traceroute.\n\
+It provides a clean, unified interface for quick network diagnostics.",
+    override_usage = "staxping [TARGET] [OPTIONS]\n  staxping <COMMAND> <TARGET> [OPTIONS]",
+    args_conflicts_with_subcommands = true,
+    help_template = "
+{name} v{version}
+{about}
+
+USAGE:
+  {usage}
+
+COMMANDS:
+{subcommands}
+
+ARGS:
+{positionals}
+
+OPTIONS:
+{options}
+"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// The target domain or IP to test
+    pub target: Option<String>,
+
+    /// Enable traceroute
+    #[arg(long)]
+    pub trace: bool,
+
+    #[command(flatten)]
+    pub dns: DnsFlags,
+
+    #[command(flatten)]
+    pub ping: PingFlags,
+
+    #[command(flatten)]
+    pub trace_flags: TraceFlags,
+
+    /// Run ping, HTTP and traceroute over both IPv4 and IPv6 and compare them
+    #[arg(long, conflicts_with_all = ["ipv4", "ipv6", "all_addresses"])]
+    pub dual_stack: bool,
+
+    /// Use IPv4 only, in every stage
+    #[arg(short = '4', long, global = true, conflicts_with = "ipv6")]
+    pub ipv4: bool,
+
+    /// Use IPv6 only, in every stage
+    #[arg(short = '6', long, global = true)]
+    pub ipv6: bool,
+
+    /// Output format
+    #[arg(long, value_enum, global = true, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Advanced mode (future)
+    #[arg(short = 'A', long)]
+    pub advanced: bool,
+}
+
+impl Cli {
+    /// The address family chosen with -4 / -6, if any
+    pub fn family(&self) -> Option<IpFamily> {
+        match (self.ipv4, self.ipv6) {
+            (true, _) => Some(IpFamily::Ipv4),
+            (_, true) => Some(IpFamily::Ipv6),
+            _ => None,
+        }
+    }
+}
+
+/// Individual diagnostic stages that can be run on their own
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Resolve a domain
+    Dns(DnsArgs),
+    /// Ping a domain or IP
+    Ping(PingArgs),
+    /// Check a URL or domain over HTTP(S)
+    Http(HttpArgs),
+    /// Trace the route to a domain or IP
+    Trace(TraceArgs),
+    /// Trace continuously, with per-hop loss and latency statistics
+    Mtr(MtrArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct DnsArgs {
+    /// The domain to resolve
+    pub target: String,
+
+    /// Query each --resolver separately and compare their answers side by side
+    #[arg(long)]
+    pub compare: bool,
+
+    /// Walk the delegation from the root servers down, like dig +trace
+    #[arg(long, conflicts_with = "compare")]
+    pub trace: bool,
+
+    /// Root server to start --trace from, as [tcp://]IP[:PORT] (repeatable); the first one's port and transport are used all the way down
+    #[arg(long = "root", value_name = "ADDR", requires = "trace")]
+    pub roots: Vec<Nameserver>,
+
+    /// named.root-style file (or one IP[:PORT] per line) listing the root servers for --trace
+    #[arg(long, value_name = "FILE", requires = "trace")]
+    pub root_hints: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub dns: DnsFlags,
+}
+
+impl DnsArgs {
+    /// Settings for --trace; the first --type is the one traced
+    pub fn delegation_options(&self) -> DelegationOptions {
+        DelegationOptions {
+            record_type: self.dns.record_types.first().copied(),
+            roots: self.roots.clone(),
+            root_hints: self.root_hints.clone(),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct PingArgs {
+    /// The domain or IP to ping
+    pub target: String,
+
+    /// Ping until Ctrl-C, printing every reply as it arrives
+    #[arg(short = 't', long, conflicts_with = "all_addresses")]
+    pub continuous: bool,
+
+    #[command(flatten)]
+    pub resolver: ResolverFlags,
+
+    #[command(flatten)]
+    pub ping: PingFlags,
+}
+
+#[derive(Args, Debug)]
+pub struct HttpArgs {
+    /// The URL or domain to check
+    pub target: String,
+}
+
+#[derive(Args, Debug)]
+pub struct TraceArgs {
+    /// The domain or IP to trace
+    pub target: String,
+
+    #[command(flatten)]
+    pub resolver: ResolverFlags,
+
+    #[command(flatten)]
+    pub trace: TraceFlags,
+}
+
+#[derive(Args, Debug)]
+pub struct MtrArgs {
+    /// The domain or IP to trace
+    pub target: String,
+
+    /// Rounds of probes to send; 0 runs until Ctrl-C
+    #[arg(short = 'c', long, default_value_t = 10)]
+    pub count: u32,
+
+    /// Milliseconds to wait between rounds
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    pub interval: u64,
+
+    #[command(flatten)]
+    pub resolver: ResolverFlags,
+
+    #[command(flatten)]
+    pub trace: TraceFlags,
+}
+
+impl MtrArgs {
+    pub fn options(&self) -> MtrOptions {
+        let mut trace = self.trace.options();
+        // mtr sends one probe per hop each round unless -q asks for more
+        trace.probes = self.trace.probes.unwrap_or(1);
+
+        MtrOptions {
+            cycles: (self.count > 0).then_some(self.count),
+            interval: Duration::from_millis(self.interval),
+            trace,
+        }
+    }
+}
+
+/// Resolver selection, for every command that resolves names
+#[derive(Args, Debug)]
+pub struct ResolverFlags {
+    /// Nameserver to query instead of the system resolver, as [tcp://]IP[:PORT] (repeatable)
+    #[arg(long = "resolver", value_name = "ADDR")]
+    pub nameservers: Vec<Nameserver>,
+
+    /// DNS-over-HTTPS server, e.g. https://dns.google/dns-query (repeatable)
+    #[arg(long, value_name = "URL", value_parser = Nameserver::parse_doh)]
+    pub doh: Vec<Nameserver>,
+
+    /// DNS-over-TLS server, e.g. 1.1.1.1 or dns.quad9.net:853 (repeatable)
+    #[arg(long, value_name = "HOST[:PORT]", value_parser = Nameserver::parse_dot)]
+    pub dot: Vec<Nameserver>,
+
+    /// PEM file with extra CA certificates to trust for --doh and --dot
+    #[arg(long, value_name = "PEM")]
+    pub dns_ca: Option<PathBuf>,
+}
+
+impl ResolverFlags {
+    pub fn options(&self) -> DnsOptions {
+        let nameservers = self
+            .nameservers
+            .iter()
+            .chain(&self.doh)
+            .chain(&self.dot)
+            .cloned()
+            .collect();
+
+        DnsOptions {
+            nameservers,
+            ca_file: self.dns_ca.clone(),
+            ..DnsOptions::default()
+        }
+    }
+}
+
+/// DNS options shared by the combined run and the `dns` command
+#[derive(Args, Debug)]
+pub struct DnsFlags {
+    #[command(flatten)]
+    pub resolver: ResolverFlags,
+
+    /// Extra record type to query, e.g. MX, TXT, NS, CNAME, SOA, SRV, CAA, PTR (repeatable)
+    #[arg(long = "type", value_name = "TYPE", value_parser = parse_record_type)]
+    pub record_types: Vec<RecordType>,
+
+    /// Validate the answers against the DNSSEC chain of trust
+    #[arg(long)]
+    pub dnssec: bool,
+
+    /// File of root DS or DNSKEY records to validate --dnssec from, replacing the built-in root keys
+    #[arg(long, value_name = "FILE", requires = "dnssec")]
+    pub trust_anchor: Option<PathBuf>,
+}
+
+impl DnsFlags {
+    pub fn options(&self) -> DnsOptions {
+        DnsOptions {
+            record_types: self.record_types.clone(),
+            dnssec: self.dnssec,
+            trust_anchor: self.trust_anchor.clone(),
+            ..self.resolver.options()
+        }
+    }
+}
+
+fn parse_record_type(value: &str) -> Result<RecordType, String> {
+    RecordType::from_str(&value.to_ascii_uppercase())
+        .map_err(|_| format!("unknown record type '{}'", value))
+}
+
+/// Ping options shared by the combined run and the `ping` command
+#[derive(Args, Debug)]
+pub struct PingFlags {
+    /// Number of echo requests to send
+    #[arg(short = 'c', long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    pub count: u32,
+
+    /// Milliseconds to wait between echo requests
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    pub interval: u64,
+
+    /// Milliseconds to wait for each reply
+    #[arg(long, value_name = "MS", default_value_t = 2000)]
+    pub timeout: u64,
+
+    /// ICMP payload size in bytes
+    #[arg(short = 's', long, value_name = "BYTES", default_value_t = 8)]
+    pub size: usize,
+
+    /// Exit with a failure code when packet loss exceeds this percentage
+    #[arg(long, value_name = "PERCENT", default_value_t = 100.0)]
+    pub max_loss: f32,
+
+    /// Ping every resolved IPv4 and IPv6 address instead of just the first
+    #[arg(long)]
+    pub all_addresses: bool,
+}
+
+impl PingFlags {
+    pub fn options(&self) -> PingOptions {
+        PingOptions {
+            count: self.count,
+            interval: Duration::from_millis(self.interval),
+            timeout: Duration::from_millis(self.timeout),
+            size: self.size,
+            continuous: false,
+        }
+    }
+}
+
+/// Traceroute options shared by the combined run and the `trace` and `mtr` commands
+#[derive(Args, Debug)]
+pub struct TraceFlags {
+    /// Number of probes to send to each hop (default 3; for mtr, 1 per round)
+    #[arg(short = 'q', long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=10))]
+    pub probes: Option<u32>,
+
+    /// Protocol to probe with; try icmp or tcp when UDP is filtered
+    #[arg(long = "trace-proto", value_name = "PROTO", value_enum, default_value_t = TraceProtocol::Udp)]
+    pub protocol: TraceProtocol,
+
+    /// Destination port of TCP probes, e.g. 443 to follow HTTPS traffic
+    #[arg(long = "trace-port", value_name = "PORT", default_value_t = 80)]
+    pub port: u16,
+
+    /// Keep every probe on one flow (Paris traceroute), so load-balanced paths stay consistent
+    #[arg(long)]
+    pub paris: bool,
+
+    /// Probe each hop over several flows and list every next hop seen (default 6 flows)
+    #[arg(
+        long,
+        value_name = "FLOWS",
+        num_args = 0..=1,
+        default_missing_value = "6",
+        conflicts_with_all = ["paris", "probes"],
+        value_parser = clap::value_parser!(u32).range(2..=32),
+    )]
+    pub multipath: Option<u32>,
+
+    /// Show hop addresses only, without looking up their names
+    #[arg(short = 'n', long)]
+    pub numeric: bool,
+}
+
+impl TraceFlags {
+    pub fn options(&self) -> TraceOptions {
+        TraceOptions {
+            probes: self.probes.unwrap_or(TraceOptions::default().probes),
+            protocol: self.protocol,
+            port: self.port,
+            paris: self.paris,
+            multipath: self.multipath,
+            numeric: self.numeric,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mtr(args: &[&str]) -> MtrOptions {
+        let cli = Cli::try_parse_from(["staxping", "mtr", "example.com"].iter().chain(args)).unwrap();
+        match cli.command {
+            Some(Command::Mtr(args)) => args.options(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn mtr_shares_the_trace_flags() {
+        let options = mtr(&[]);
+        assert_eq!(options.trace.probes, 1);
+        assert_eq!((options.trace.protocol, options.trace.port), (TraceProtocol::Udp, 80));
+
+        let options = mtr(&["-q", "2", "--trace-proto", "tcp", "--trace-port", "443", "--paris", "-n"]);
+        assert_eq!(options.trace.probes, 2);
+        assert_eq!((options.trace.protocol, options.trace.port), (TraceProtocol::Tcp, 443));
+        assert!(options.trace.paris && options.trace.numeric);
+
+        let cli = Cli::try_parse_from(["staxping", "trace", "example.com"]).unwrap();
+        let Some(Command::Trace(args)) = cli.command else { unreachable!() };
+        assert_eq!(args.trace.options().probes, 3);
+    }
+}
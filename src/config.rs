@@ -51,11 +51,6 @@ impl Config {
         }
     }
 
-    /// Checks if the config file exists.
-    pub fn exists() -> bool {
-        Config::path().exists()
-    }
-
     /// Loads the config file if it exists.
     pub fn load() -> Option<Self> {
         let path = Config::path();
@@ -0,0 +1,677 @@
+// StaxPing — Unified Network Diagnostics
+// Copyright (c) 2026 StaxDash
+//
+// This source code is provided under the StaxPing Source‑Available License & EULA.
+// You may view, modify, and redistribute this code for personal or internal use.
+// Commercial use of any kind requires explicit written permission from StaxDash.
+//
+// Full license text available in LICENSE and EULA.md.
+
+use trust_dns_resolver::{
+    proto::{
+        op::{Message, ResponseCode},
+        rr::{RData, RecordType},
+    },
+    Name,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::dns::{raw_query, DnsRecord, DnsStatus, Nameserver, QueryFlags, RawServer};
+
+/// Iterative queries go straight to authoritative servers, one hop at a time
+const FLAGS: QueryFlags = QueryFlags {
+    recursion_desired: false,
+    dnssec_ok: false,
+    checking_disabled: false,
+    timeout: Duration::from_secs(2),
+};
+
+/// Guards against referral loops
+const MAX_STEPS: usize = 16;
+
+/// How deep glueless nameserver lookups may nest
+const MAX_GLUELESS_DEPTH: u8 = 4;
+
+/// IANA root servers (IPv4), used when no root hints are given
+const ROOT_SERVERS: [(&str, &str); 13] = [
+    ("a.root-servers.net.", "198.41.0.4"),
+    ("b.root-servers.net.", "170.247.170.2"),
+    ("c.root-servers.net.", "192.33.4.12"),
+    ("d.root-servers.net.", "199.7.91.13"),
+    ("e.root-servers.net.", "192.203.230.10"),
+    ("f.root-servers.net.", "192.5.5.241"),
+    ("g.root-servers.net.", "192.112.36.4"),
+    ("h.root-servers.net.", "198.97.190.53"),
+    ("i.root-servers.net.", "192.36.148.17"),
+    ("j.root-servers.net.", "192.58.128.30"),
+    ("k.root-servers.net.", "193.0.14.129"),
+    ("l.root-servers.net.", "199.7.83.42"),
+    ("m.root-servers.net.", "202.12.27.33"),
+];
+
+/// Settings for `dns --trace`
+#[derive(Debug, Clone, Default)]
+pub struct DelegationOptions {
+    pub record_type: Option<RecordType>,
+    /// Root servers to start from instead of the built-in list
+    pub roots: Vec<Nameserver>,
+    /// named.root-style file (or one address per line) with root servers
+    pub root_hints: Option<PathBuf>,
+}
+
+/// The walk from the root down to the authoritative answer
+#[derive(Serialize, Debug, Clone)]
+pub struct DelegationTrace {
+    pub record_type: String,
+    pub steps: Vec<DelegationStep>,
+    /// Outcome of the final, authoritative query
+    pub status: DnsStatus,
+    pub answers: Vec<DnsRecord>,
+    /// Why the walk stopped before reaching an authoritative answer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// One zone on the way down, with every one of its servers asked
+#[derive(Serialize, Debug, Clone)]
+pub struct DelegationStep {
+    pub zone: String,
+    pub servers: Vec<ServerProbe>,
+    /// The NS set handed out for the next zone, if this step was a referral
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub referral: Option<Referral>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Referral {
+    pub zone: String,
+    pub nameservers: Vec<DelegatedServer>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DelegatedServer {
+    pub name: String,
+    /// Addresses from the additional section; empty for glueless delegations
+    pub glue: Vec<String>,
+}
+
+/// How one server of a zone answered
+#[derive(Serialize, Debug, Clone)]
+pub struct ServerProbe {
+    pub name: String,
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_ms: Option<u128>,
+    pub outcome: ProbeOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeOutcome {
+    /// Delegated further down towards the name
+    Referral,
+    /// Answered authoritatively, positively or negatively
+    Answer,
+    /// Listed as a nameserver for the zone but does not serve it
+    Lame,
+    /// Unreachable or timed out
+    Unreachable,
+}
+
+impl ProbeOutcome {
+    fn usable(self) -> bool {
+        matches!(self, ProbeOutcome::Referral | ProbeOutcome::Answer)
+    }
+}
+
+/// A nameserver to ask, by name and address
+#[derive(Debug, Clone)]
+struct Server {
+    name: String,
+    raw: RawServer,
+}
+
+/// What a single response means for the walk
+enum Reply {
+    Referral(Name, Vec<DelegatedServer>),
+    Answer(Message),
+    Lame(String),
+}
+
+/// Walks the delegation chain for `domain` from the root, like `dig +trace`,
+/// asking every server of each zone so lame delegations stand out.
+pub async fn trace_delegation(domain: &str, options: &DelegationOptions) -> Result<DelegationTrace, String> {
+    let mut name = Name::from_utf8(domain).map_err(|e| format!("Invalid name: {}", e))?;
+    name.set_fqdn(true);
+    let record_type = options.record_type.unwrap_or(RecordType::A);
+
+    let roots = root_servers(options)?;
+    let mut walker = Walker {
+        roots: roots.clone(),
+        addresses: HashMap::new(),
+        port: roots[0].raw.addr.port(),
+        tcp: roots[0].raw.tcp,
+    };
+
+    let mut trace = DelegationTrace {
+        record_type: record_type.to_string(),
+        steps: Vec::new(),
+        status: DnsStatus::Error,
+        answers: Vec::new(),
+        error: None,
+    };
+
+    let mut zone = Name::root();
+    let mut servers = roots;
+
+    for _ in 0..MAX_STEPS {
+        let probes = probe_all(&servers, &zone, &name, record_type).await;
+
+        let mut step = DelegationStep {
+            zone: zone.to_string(),
+            servers: Vec::new(),
+            referral: None,
+        };
+        let mut next = None;
+        for (probe, reply) in probes {
+            if next.is_none() && probe.outcome.usable() {
+                next = reply;
+            }
+            step.servers.push(probe);
+        }
+
+        match next {
+            Some(Reply::Referral(child, nameservers)) => {
+                step.referral = Some(Referral {
+                    zone: child.to_string(),
+                    nameservers: nameservers.clone(),
+                });
+                trace.steps.push(step);
+
+                servers = walker.addresses_of(&nameservers, 0).await;
+                if servers.is_empty() {
+                    trace.error = Some(format!("No address found for any nameserver of {}", child));
+                    return Ok(trace);
+                }
+                zone = child;
+            }
+            Some(Reply::Answer(response)) => {
+                trace.steps.push(step);
+                trace.status = answer_status(&response);
+                trace.answers = response.answers().iter().filter_map(DnsRecord::from_record).collect();
+                return Ok(trace);
+            }
+            Some(Reply::Lame(_)) | None => {
+                trace.steps.push(step);
+                trace.error = Some(format!("No server for {} gave a usable answer", zone));
+                return Ok(trace);
+            }
+        }
+    }
+
+    trace.error = Some(format!("Gave up after {} referrals", MAX_STEPS));
+    Ok(trace)
+}
+
+/// Asks every server of `zone` at once, keeping their order.
+async fn probe_all(
+    servers: &[Server],
+    zone: &Name,
+    name: &Name,
+    record_type: RecordType,
+) -> Vec<(ServerProbe, Option<Reply>)> {
+    let handles: Vec<_> = servers
+        .iter()
+        .map(|server| {
+            let raw = server.raw;
+            let name = name.clone();
+            tokio::spawn(async move {
+                let start = Instant::now();
+                let response = raw_query(raw, &name, record_type, FLAGS).await;
+                (response, start.elapsed().as_millis())
+            })
+        })
+        .collect();
+
+    let mut probes = Vec::new();
+    for (server, handle) in servers.iter().zip(handles) {
+        let (response, time_ms) = match handle.await {
+            Ok(result) => result,
+            Err(e) => (Err(format!("Query task failed: {}", e)), 0),
+        };
+
+        let mut probe = ServerProbe {
+            name: server.name.clone(),
+            address: server.raw.addr.ip().to_string(),
+            time_ms: None,
+            outcome: ProbeOutcome::Unreachable,
+            detail: None,
+        };
+
+        let reply = match response {
+            Ok(response) => {
+                probe.time_ms = Some(time_ms);
+                let reply = classify(zone, name, response);
+                match &reply {
+                    Reply::Referral(child, _) => {
+                        probe.outcome = ProbeOutcome::Referral;
+                        probe.detail = Some(format!("referral to {}", child));
+                    }
+                    Reply::Answer(response) => {
+                        probe.outcome = ProbeOutcome::Answer;
+                        probe.detail = Some(answer_status(response).to_string());
+                    }
+                    Reply::Lame(reason) => {
+                        probe.outcome = ProbeOutcome::Lame;
+                        probe.detail = Some(reason.clone());
+                    }
+                }
+                Some(reply)
+            }
+            Err(e) => {
+                probe.detail = Some(e);
+                None
+            }
+        };
+
+        probes.push((probe, reply));
+    }
+
+    probes
+}
+
+/// Sorts a response from a server of `zone` into referral, answer or lame.
+fn classify(zone: &Name, name: &Name, response: Message) -> Reply {
+    match response.response_code() {
+        ResponseCode::NoError | ResponseCode::NXDomain => {}
+        code => return Reply::Lame(code.to_string()),
+    }
+
+    if response.authoritative() {
+        return Reply::Answer(response);
+    }
+
+    // A referral names a zone strictly between the current one and the name
+    let child = response
+        .name_servers()
+        .iter()
+        .filter(|record| record.record_type() == RecordType::NS)
+        .map(|record| record.name().clone())
+        .find(|child| zone.zone_of(child) && child != zone && child.zone_of(name));
+
+    let Some(child) = child else {
+        return if response.answers().is_empty() {
+            Reply::Lame("not authoritative, no referral".into())
+        } else {
+            Reply::Lame("answered without authority".into())
+        };
+    };
+
+    let nameservers = response
+        .name_servers()
+        .iter()
+        .filter(|record| record.record_type() == RecordType::NS && *record.name() == child)
+        .filter_map(|record| match record.data() {
+            Some(RData::NS(ns)) => Some(ns.0.clone()),
+            _ => None,
+        })
+        .map(|ns| DelegatedServer {
+            name: ns.to_string(),
+            glue: response
+                .additionals()
+                .iter()
+                .filter(|record| *record.name() == ns)
+                .filter_map(|record| match record.data() {
+                    Some(RData::A(a)) => Some(a.0.to_string()),
+                    Some(RData::AAAA(aaaa)) => Some(aaaa.0.to_string()),
+                    _ => None,
+                })
+                .collect(),
+        })
+        .collect();
+
+    Reply::Referral(child, nameservers)
+}
+
+fn answer_status(response: &Message) -> DnsStatus {
+    match response.response_code() {
+        ResponseCode::NXDomain => DnsStatus::NxDomain,
+        _ if response.answers().is_empty() => DnsStatus::NoData,
+        _ => DnsStatus::NoError,
+    }
+}
+
+/// Resolves nameserver addresses on its own, from the same roots, so the
+/// trace never depends on the system resolver.
+struct Walker {
+    roots: Vec<Server>,
+    addresses: HashMap<Name, Option<IpAddr>>,
+    /// Port and transport of the first root, used for every server below
+    /// it too, so a whole test hierarchy can run on one unprivileged port
+    port: u16,
+    tcp: bool,
+}
+
+impl Walker {
+    /// One address per nameserver: IPv4 glue first, then IPv6 glue, then a lookup.
+    async fn addresses_of(&mut self, nameservers: &[DelegatedServer], depth: u8) -> Vec<Server> {
+        let mut servers = Vec::new();
+        for nameserver in nameservers {
+            let glue = nameserver.glue.iter().filter_map(|ip| ip.parse::<IpAddr>().ok());
+            let ip = match glue.min_by_key(|ip| ip.is_ipv6()) {
+                Some(ip) => Some(ip),
+                None => match Name::from_utf8(&nameserver.name) {
+                    Ok(name) => self.resolve(name, depth).await,
+                    Err(_) => None,
+                },
+            };
+
+            if let Some(ip) = ip {
+                servers.push(Server {
+                    name: nameserver.name.clone(),
+                    raw: RawServer { addr: SocketAddr::new(ip, self.port), tcp: self.tcp },
+                });
+            }
+        }
+        servers
+    }
+
+    /// Iteratively looks up the address of a glueless nameserver, taking the
+    /// first usable server at each step. IPv4 is preferred; a nameserver
+    /// without A records is asked for its AAAA records instead.
+    fn resolve(&mut self, name: Name, depth: u8) -> Pin<Box<dyn Future<Output = Option<IpAddr>> + '_>> {
+        Box::pin(async move {
+            if let Some(ip) = self.addresses.get(&name) {
+                return *ip;
+            }
+            if depth >= MAX_GLUELESS_DEPTH {
+                return None;
+            }
+
+            let mut zone = Name::root();
+            let mut servers = self.roots.clone();
+            let mut ip = None;
+
+            'walk: for _ in 0..MAX_STEPS {
+                let mut reply = None;
+                for server in &servers {
+                    if let Ok(response) = raw_query(server.raw, &name, RecordType::A, FLAGS).await {
+                        match classify(&zone, &name, response) {
+                            Reply::Lame(_) => continue,
+                            usable => {
+                                reply = Some(usable);
+                                break;
+                            }
+                        }
+                    }
+                }
+
+                match reply {
+                    Some(Reply::Referral(child, nameservers)) => {
+                        servers = self.addresses_of(&nameservers, depth + 1).await;
+                        zone = child;
+                    }
+                    Some(Reply::Answer(response)) => {
+                        ip = first_address(&response);
+                        if ip.is_none() {
+                            for server in &servers {
+                                if let Ok(response) = raw_query(server.raw, &name, RecordType::AAAA, FLAGS).await {
+                                    if let Reply::Answer(response) = classify(&zone, &name, response) {
+                                        ip = first_address(&response);
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                        break 'walk;
+                    }
+                    _ => break 'walk,
+                }
+            }
+
+            self.addresses.insert(name, ip);
+            ip
+        })
+    }
+}
+
+/// The first A or AAAA address in the answer section
+fn first_address(response: &Message) -> Option<IpAddr> {
+    response.answers().iter().find_map(|record| match record.data() {
+        Some(RData::A(a)) => Some(IpAddr::V4(a.0)),
+        Some(RData::AAAA(aaaa)) => Some(IpAddr::V6(aaaa.0)),
+        _ => None,
+    })
+}
+
+/// The servers to start from: `--root`, then `--root-hints`, then the built-in list.
+fn root_servers(options: &DelegationOptions) -> Result<Vec<Server>, String> {
+    let mut servers: Vec<Server> = options
+        .roots
+        .iter()
+        .map(|root| {
+            root.raw_server()
+                .map(|raw| Server { name: raw.addr.ip().to_string(), raw })
+                .ok_or_else(|| format!("Root server must be a plain UDP or TCP address, got '{}'", root))
+        })
+        .collect::<Result<_, _>>()?;
+
+    if let Some(path) = &options.root_hints {
+        let hints = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read root hints {}: {}", path.display(), e))?;
+        servers.extend(parse_root_hints(&hints)?);
+    }
+
+    if servers.is_empty() {
+        servers = ROOT_SERVERS
+            .iter()
+            .map(|(name, ip)| Server {
+                name: name.to_string(),
+                raw: RawServer { addr: SocketAddr::new(ip.parse().unwrap(), 53), tcp: false },
+            })
+            .collect();
+    }
+
+    Ok(servers)
+}
+
+/// Reads a named.root-style hints file ("A.ROOT-SERVERS.NET. 3600000 A 198.41.0.4")
+/// or a plain list of IP[:PORT] addresses, one per line. For named servers the
+/// IPv4 address wins over the IPv6 one.
+fn parse_root_hints(hints: &str) -> Result<Vec<Server>, String> {
+    let mut servers: Vec<Server> = Vec::new();
+
+    for line in hints.lines() {
+        let line = line.split([';', '#']).next().unwrap_or("").trim();
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        match fields.as_slice() {
+            [] => continue,
+            [address] => {
+                let raw = Nameserver::from_str(address)
+                    .ok()
+                    .and_then(|nameserver| nameserver.raw_server())
+                    .ok_or_else(|| format!("Invalid root hint '{}'", address))?;
+                servers.push(Server { name: raw.addr.ip().to_string(), raw });
+            }
+            [name, .., record_type, address] if record_type.eq_ignore_ascii_case("A") || record_type.eq_ignore_ascii_case("AAAA") => {
+                let ip = address
+                    .parse::<IpAddr>()
+                    .map_err(|_| format!("Invalid root hint address '{}'", address))?;
+                let name = name.to_ascii_lowercase();
+                let raw = RawServer { addr: SocketAddr::new(ip, 53), tcp: false };
+
+                match servers.iter_mut().find(|server| server.name == name) {
+                    Some(server) if server.raw.addr.is_ipv6() && ip.is_ipv4() => server.raw = raw,
+                    Some(_) => {}
+                    None => servers.push(Server { name, raw }),
+                }
+            }
+            // NS lines and anything else in named.root carry no addresses
+            _ => continue,
+        }
+    }
+
+    if servers.is_empty() {
+        return Err("Root hints list no server addresses".into());
+    }
+    Ok(servers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, StubDns};
+    use std::io;
+    use std::net::Ipv4Addr;
+
+    type Zone = fn(&Message) -> Message;
+
+    /// Refers everything to example., served by a.nic (127.0.0.2) and the
+    /// lame b.nic (127.0.0.4)
+    fn root_zone(request: &Message) -> Message {
+        let mut response = testing::reply(request, ResponseCode::NoError);
+        response
+            .add_name_server(testing::ns("example.", "a.nic.example."))
+            .add_name_server(testing::ns("example.", "b.nic.example."))
+            .add_additional(testing::a("a.nic.example.", Ipv4Addr::new(127, 0, 0, 2)))
+            .add_additional(testing::a("b.nic.example.", Ipv4Addr::new(127, 0, 0, 4)));
+        response
+    }
+
+    /// Refers lab.example. to ns1 (glue 127.0.0.3) and to ns6.v6.example.,
+    /// which has no glue and only an IPv6 address
+    fn tld_zone(request: &Message) -> Message {
+        let query = &request.queries()[0];
+        let mut response = testing::reply(request, ResponseCode::NoError);
+
+        if testing::name("lab.example.").zone_of(query.name()) {
+            response
+                .add_name_server(testing::ns("lab.example.", "ns1.lab.example."))
+                .add_name_server(testing::ns("lab.example.", "ns6.v6.example."))
+                .add_additional(testing::a("ns1.lab.example.", Ipv4Addr::new(127, 0, 0, 3)));
+            return response;
+        }
+
+        response.set_authoritative(true);
+        if *query.name() == testing::name("ns6.v6.example.") {
+            if query.query_type() == RecordType::AAAA {
+                response.add_answer(testing::aaaa("ns6.v6.example.", "::1".parse().unwrap()));
+            }
+        } else {
+            response.set_response_code(ResponseCode::NXDomain);
+        }
+        response
+    }
+
+    fn lab_zone(request: &Message) -> Message {
+        let query = &request.queries()[0];
+        let mut response = testing::reply(request, ResponseCode::NoError);
+        response.set_authoritative(true);
+        if *query.name() == testing::name("www.lab.example.") && query.query_type() == RecordType::A {
+            response.add_answer(testing::a("www.lab.example.", Ipv4Addr::new(192, 0, 2, 80)));
+        } else if *query.name() != testing::name("www.lab.example.") {
+            response.set_response_code(ResponseCode::NXDomain);
+        }
+        response
+    }
+
+    fn lame(request: &Message) -> Message {
+        testing::reply(request, ResponseCode::Refused)
+    }
+
+    /// Every server of the hierarchy on its own loopback address, all on
+    /// the root's port. Errs, naming the address, on hosts that cannot
+    /// bind 127.0.0.2-4 (macOS without loopback aliases) or ::1.
+    async fn hierarchy() -> Result<Vec<StubDns>, String> {
+        'retry: for _ in 0..testing::BIND_ATTEMPTS {
+            let root = StubDns::start(root_zone).await;
+            let port = root.addr.port();
+            let mut stubs = vec![root];
+
+            let zones: [(&str, Zone); 4] =
+                [("127.0.0.2", tld_zone), ("127.0.0.3", lab_zone), ("::1", lab_zone), ("127.0.0.4", lame)];
+            for (ip, zone) in zones {
+                let addr = SocketAddr::new(ip.parse().unwrap(), port);
+                match StubDns::start_at(addr, zone).await {
+                    Ok(stub) => stubs.push(stub),
+                    // Someone else has the root's port on this address
+                    Err(e) if e.kind() == io::ErrorKind::AddrInUse => continue 'retry,
+                    Err(e) => return Err(format!("cannot serve {}: {}", addr, e)),
+                }
+            }
+            return Ok(stubs);
+        }
+        panic!("No port free on every loopback address after {} attempts", testing::BIND_ATTEMPTS);
+    }
+
+    /// The hierarchy, or None after saying why this host cannot run it
+    async fn hierarchy_or_skip() -> Option<Vec<StubDns>> {
+        match hierarchy().await {
+            Ok(stubs) => Some(stubs),
+            Err(e) => {
+                eprintln!("skipping the stub hierarchy test: {}", e);
+                None
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn walks_a_local_hierarchy() {
+        let Some(stubs) = hierarchy_or_skip().await else {
+            return;
+        };
+
+        for root in [format!("127.0.0.1:{}", stubs[0].addr.port()), format!("tcp://127.0.0.1:{}", stubs[0].addr.port())] {
+            let options = DelegationOptions { roots: vec![root.parse().unwrap()], ..DelegationOptions::default() };
+            let trace = trace_delegation("www.lab.example", &options).await.unwrap();
+
+            assert_eq!(trace.error, None);
+            assert_eq!(trace.status, DnsStatus::NoError);
+            assert_eq!(trace.answers[0].data, "192.0.2.80");
+
+            let zones: Vec<&str> = trace.steps.iter().map(|step| step.zone.as_str()).collect();
+            assert_eq!(zones, [".", "example.", "lab.example."]);
+
+            // b.nic refuses to serve example.
+            let tld = &trace.steps[1];
+            assert_eq!(tld.servers[0].outcome, ProbeOutcome::Referral);
+            assert_eq!(tld.servers[1].outcome, ProbeOutcome::Lame);
+
+            // ns6 has neither glue nor an A record, so it is found by its AAAA
+            let referral = tld.referral.as_ref().unwrap();
+            assert_eq!(referral.nameservers[1].name, "ns6.v6.example.");
+            assert!(referral.nameservers[1].glue.is_empty());
+
+            let lab = &trace.steps[2];
+            let addresses: Vec<&str> = lab.servers.iter().map(|server| server.address.as_str()).collect();
+            assert_eq!(addresses, ["127.0.0.3", "::1"]);
+            assert!(lab.servers.iter().all(|server| server.outcome == ProbeOutcome::Answer));
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_nxdomain_from_the_authoritative_server() {
+        let Some(stubs) = hierarchy_or_skip().await else {
+            return;
+        };
+        let options = DelegationOptions {
+            roots: vec![format!("127.0.0.1:{}", stubs[0].addr.port()).parse().unwrap()],
+            ..DelegationOptions::default()
+        };
+
+        let trace = trace_delegation("missing.lab.example", &options).await.unwrap();
+        assert_eq!(trace.status, DnsStatus::NxDomain);
+        assert!(trace.answers.is_empty());
+    }
+}
@@ -9,45 +9,1340 @@
 
 use trust_dns_resolver::{
     TokioAsyncResolver,
+    system_conf::read_system_conf,
+    config::{NameServerConfig, NameServerConfigGroup, Protocol, ResolverConfig, ResolverOpts},
+    error::{ResolveError, ResolveErrorKind},
     name_server::{GenericConnector, TokioRuntimeProvider},
+    proto::{
+        error::ProtoErrorKind,
+        op::{Edns, Message, MessageType, OpCode, Query, ResponseCode},
+        rr::{RData, Record, RecordType},
+    },
+    Name,
 };
-use std::time::Instant;
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
 
+use crate::dnssec::{DnssecResult, TrustAnchors, Validator};
+
+#[derive(Serialize, Debug, Clone)]
 pub struct DnsResult {
+    /// The nameservers that answered, or "system" for the OS resolver
+    pub resolver: String,
+    pub transport: Transport,
+    /// Connection setup cost for DoT and DoH
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handshake_ms: Option<u128>,
     pub ipv4: Vec<String>,
     pub ipv6: Vec<String>,
     pub lookup_ms: u128,
+    /// Every record set that was queried, A and AAAA included unless the
+    /// target is an IP
+    pub answers: Vec<RecordSet>,
+    /// PTR names for the target (if it is an IP) and every resolved address
+    pub reverse: Vec<ReverseLookup>,
+}
+
+/// The PTR names of one address
+#[derive(Serialize, Debug, Clone)]
+pub struct ReverseLookup {
+    pub ip: String,
+    pub status: DnsStatus,
+    pub names: Vec<String>,
+    pub fcrdns: FcrDns,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Forward-confirmed reverse DNS: whether a PTR name resolves back to the address
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FcrDns {
+    Confirmed,
+    /// PTR names exist but none of them resolves to the address
+    Mismatch,
+    NoPtr,
+}
+
+/// The answer to a single query type
+#[derive(Serialize, Debug, Clone)]
+pub struct RecordSet {
+    pub record_type: String,
+    pub status: DnsStatus,
+    pub records: Vec<DnsRecord>,
+    /// Lowest TTL in the answer, CNAMEs included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+    /// How long this query took on its own
+    pub lookup_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// DNSSEC validation outcome, when validation was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dnssec: Option<DnssecResult>,
+}
+
+/// Why a query did or did not produce records
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum DnsStatus {
+    /// Records were returned
+    NoError,
+    /// The name does not exist
+    NxDomain,
+    /// The name exists but has no records of this type
+    NoData,
+    /// The server failed to answer
+    ServFail,
+    /// The server refused to answer
+    Refused,
+    /// No response before the resolver gave up
+    Timeout,
+    /// Any other failure (bad name, network error, unusual response code)
+    Error,
+}
+
+impl DnsStatus {
+    /// Classifies a resolver error
+    fn from_error(error: &ResolveError) -> Self {
+        match error.kind() {
+            ResolveErrorKind::NoRecordsFound { response_code, .. } => match *response_code {
+                ResponseCode::NXDomain => DnsStatus::NxDomain,
+                ResponseCode::NoError => DnsStatus::NoData,
+                ResponseCode::ServFail => DnsStatus::ServFail,
+                ResponseCode::Refused => DnsStatus::Refused,
+                _ => DnsStatus::Error,
+            },
+            ResolveErrorKind::Timeout => DnsStatus::Timeout,
+            ResolveErrorKind::Proto(e) if matches!(e.kind(), ProtoErrorKind::Timeout) => {
+                DnsStatus::Timeout
+            }
+            _ => DnsStatus::Error,
+        }
+    }
+
+    /// Whether the server gave an answer, even an empty one
+    pub fn answered(&self) -> bool {
+        matches!(self, DnsStatus::NoError | DnsStatus::NxDomain | DnsStatus::NoData)
+    }
+
+    /// One-line explanation for the text report
+    pub fn describe(&self) -> &'static str {
+        match self {
+            DnsStatus::NoError => "answered",
+            DnsStatus::NxDomain => "name does not exist",
+            DnsStatus::NoData => "no records of this type",
+            DnsStatus::ServFail => "server failed to answer",
+            DnsStatus::Refused => "server refused the query",
+            DnsStatus::Timeout => "no response from server",
+            DnsStatus::Error => "lookup failed",
+        }
+    }
+}
+
+impl fmt::Display for DnsStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DnsStatus::NoError => "NOERROR",
+            DnsStatus::NxDomain => "NXDOMAIN",
+            DnsStatus::NoData => "NODATA",
+            DnsStatus::ServFail => "SERVFAIL",
+            DnsStatus::Refused => "REFUSED",
+            DnsStatus::Timeout => "TIMEOUT",
+            DnsStatus::Error => "ERROR",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DnsRecord {
+    pub name: String,
+    pub record_type: String,
+    pub ttl: u32,
+    pub data: String,
+}
+
+impl DnsRecord {
+    pub fn from_record(record: &Record) -> Option<Self> {
+        record.data().map(|data| DnsRecord {
+            name: record.name().to_string(),
+            record_type: record.record_type().to_string(),
+            ttl: record.ttl(),
+            data: format_rdata(data),
+        })
+    }
+}
+
+/// How queries reach a nameserver
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Udp,
+    Tcp,
+    /// DNS-over-TLS
+    Tls,
+    /// DNS-over-HTTPS
+    Https,
+}
+
+impl Transport {
+    fn default_port(self) -> u16 {
+        match self {
+            Transport::Udp | Transport::Tcp => 53,
+            Transport::Tls => 853,
+            Transport::Https => 443,
+        }
+    }
+
+    fn is_encrypted(self) -> bool {
+        matches!(self, Transport::Tls | Transport::Https)
+    }
+
+    fn protocol(self) -> Protocol {
+        match self {
+            Transport::Udp => Protocol::Udp,
+            Transport::Tcp => Protocol::Tcp,
+            Transport::Tls => Protocol::Tls,
+            Transport::Https => Protocol::Https,
+        }
+    }
+
+    /// Name for the text report
+    pub fn describe(self) -> &'static str {
+        match self {
+            Transport::Udp => "UDP",
+            Transport::Tcp => "TCP",
+            Transport::Tls => "DNS-over-TLS",
+            Transport::Https => "DNS-over-HTTPS",
+        }
+    }
+}
+
+/// A user-supplied nameserver.
+///
+/// Parsed from `[udp://|tcp://|tls://|https://]HOST[:PORT][/dns-query]`,
+/// where HOST is an IP or, for bootstrapping encrypted transports, a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nameserver {
+    pub host: String,
+    pub port: u16,
+    pub transport: Transport,
+}
+
+impl Nameserver {
+    /// Parses a `--doh` URL; the scheme may be omitted
+    pub fn parse_doh(value: &str) -> Result<Self, String> {
+        if value.contains("://") && !value.starts_with("https://") {
+            return Err(format!("DoH server must be an https:// URL, got '{}'", value));
+        }
+        Self::parse_with(value, Transport::Https)
+    }
+
+    /// Parses a `--dot` HOST[:PORT]
+    pub fn parse_dot(value: &str) -> Result<Self, String> {
+        if value.contains("://") && !value.starts_with("tls://") {
+            return Err(format!("DoT server must be HOST[:PORT], got '{}'", value));
+        }
+        Self::parse_with(value, Transport::Tls)
+    }
+
+    fn parse_with(value: &str, default: Transport) -> Result<Self, String> {
+        let (transport, rest) = match value.split_once("://") {
+            Some(("udp", rest)) => (Transport::Udp, rest),
+            Some(("tcp", rest)) => (Transport::Tcp, rest),
+            Some(("tls", rest)) => (Transport::Tls, rest),
+            Some(("https", rest)) => (Transport::Https, rest),
+            Some((scheme, _)) => return Err(format!("unsupported nameserver scheme '{}'", scheme)),
+            None => (default, value),
+        };
+
+        // trust-dns always posts to /dns-query, so other DoH paths cannot work
+        let (authority, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, ""),
+        };
+        let path_ok = path.is_empty() || (transport == Transport::Https && path == "/dns-query");
+        if !path_ok {
+            return Err(format!("unsupported nameserver path '{}' (only /dns-query)", path));
+        }
+
+        let (host, port) = split_host_port(authority)
+            .ok_or_else(|| format!("invalid nameserver '{}'", value))?;
+
+        // Plain DNS cannot bootstrap itself, so it needs an address
+        if !transport.is_encrypted() && host.parse::<IpAddr>().is_err() {
+            return Err(format!("nameserver '{}' must be an IP address", value));
+        }
+
+        Ok(Nameserver {
+            host,
+            port: port.unwrap_or(transport.default_port()),
+            transport,
+        })
+    }
+
+    /// Addresses to contact; names are looked up with the system resolver
+    async fn addresses(&self) -> Result<Vec<IpAddr>, String> {
+        if let Ok(ip) = self.host.parse::<IpAddr>() {
+            return Ok(vec![ip]);
+        }
+
+        let connector = GenericConnector::new(TokioRuntimeProvider::default());
+        let bootstrap = TokioAsyncResolver::from_system_conf(connector)
+            .map_err(|e| format!("Resolver init failed: {}", e))?;
+
+        let lookup = bootstrap
+            .lookup_ip(self.host.as_str())
+            .await
+            .map_err(|e| format!("Could not resolve nameserver {}: {}", self.host, e))?;
+
+        Ok(lookup.iter().collect())
+    }
+
+    /// The address for hand-built queries, for plain UDP/TCP nameservers only
+    pub fn raw_server(&self) -> Option<RawServer> {
+        let ip = self.host.parse::<IpAddr>().ok()?;
+        let tcp = match self.transport {
+            Transport::Udp => false,
+            Transport::Tcp => true,
+            Transport::Tls | Transport::Https => return None,
+        };
+        Some(RawServer { addr: SocketAddr::new(ip, self.port), tcp })
+    }
+}
+
+/// Splits "host", "host:port", "v4:port", "v6" and "[v6]:port"
+fn split_host_port(value: &str) -> Option<(String, Option<u16>)> {
+    if let Some(rest) = value.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']')?;
+        let port = match rest.strip_prefix(':') {
+            Some(port) => Some(port.parse().ok()?),
+            None if rest.is_empty() => None,
+            None => return None,
+        };
+        return Some((host.to_string(), port));
+    }
+
+    if value.matches(':').count() > 1 {
+        return Some((value.to_string(), None));
+    }
+
+    match value.split_once(':') {
+        Some((host, port)) if !host.is_empty() => Some((host.to_string(), Some(port.parse().ok()?))),
+        Some(_) => None,
+        None if !value.is_empty() => Some((value.to_string(), None)),
+        None => None,
+    }
+}
+
+impl FromStr for Nameserver {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse_with(value, Transport::Udp)
+    }
 }
 
-pub async fn resolve_domain(domain: &str) -> Result<DnsResult, String> {
+impl fmt::Display for Nameserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+
+        match self.transport {
+            Transport::Udp => write!(f, "{}:{}", host, self.port),
+            Transport::Tcp => write!(f, "tcp://{}:{}", host, self.port),
+            Transport::Tls => write!(f, "tls://{}:{}", host, self.port),
+            Transport::Https => write!(f, "https://{}:{}/dns-query", host, self.port),
+        }
+    }
+}
+
+/// Lookup settings for the DNS stage
+#[derive(Debug, Clone, Default)]
+pub struct DnsOptions {
+    /// Extra record types to query on top of A and AAAA
+    pub record_types: Vec<RecordType>,
+    /// Nameservers to query instead of the system resolver
+    pub nameservers: Vec<Nameserver>,
+    /// Extra PEM certificates to trust for DoT and DoH
+    pub ca_file: Option<PathBuf>,
+    /// Validate every record set against the DNSSEC chain of trust
+    pub dnssec: bool,
+    /// Root DS or DNSKEY records to validate from instead of the built-in ones
+    pub trust_anchor: Option<PathBuf>,
+    /// Only look up addresses of this family
+    pub family: Option<IpFamily>,
+}
+
+/// An address family, for -4 / -6 and dual-stack runs
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IpFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl IpFamily {
+    pub fn matches(self, ip: &IpAddr) -> bool {
+        match self {
+            IpFamily::Ipv4 => ip.is_ipv4(),
+            IpFamily::Ipv6 => ip.is_ipv6(),
+        }
+    }
+
+    /// The record type holding addresses of this family
+    pub fn record_type(self) -> RecordType {
+        match self {
+            IpFamily::Ipv4 => RecordType::A,
+            IpFamily::Ipv6 => RecordType::AAAA,
+        }
+    }
+}
+
+impl fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpFamily::Ipv4 => f.write_str("IPv4"),
+            IpFamily::Ipv6 => f.write_str("IPv6"),
+        }
+    }
+}
+
+/// Builds a resolver for the configured nameservers, or the system one.
+async fn build_resolver(options: &DnsOptions) -> Result<TokioAsyncResolver, String> {
     // Build the required connector
     let connector = GenericConnector::new(TokioRuntimeProvider::default());
 
-    // Correct constructor for trust-dns-resolver
-    let resolver = TokioAsyncResolver::from_system_conf(connector)
-        .map_err(|e| format!("Resolver init failed: {}", e))?;
+    if options.nameservers.is_empty() {
+        // Correct constructor for trust-dns-resolver
+        return TokioAsyncResolver::from_system_conf(connector)
+            .map_err(|e| format!("Resolver init failed: {}", e));
+    }
+
+    let mut group = NameServerConfigGroup::new();
+    for nameserver in &options.nameservers {
+        for ip in nameserver.addresses().await? {
+            let addr = SocketAddr::new(ip, nameserver.port);
+            let mut config = NameServerConfig::new(addr, nameserver.transport.protocol());
+            if nameserver.transport.is_encrypted() {
+                config.tls_dns_name = Some(nameserver.host.clone());
+            }
+            group.push(config);
+        }
+    }
 
+    let mut config = ResolverConfig::from_parts(None, vec![], group);
+    if let Some(ca_file) = &options.ca_file {
+        config.set_tls_client_config(Arc::new(tls_client_config(ca_file)?));
+    }
+
+    // Only ask the servers we were given, never /etc/hosts
+    let mut opts = ResolverOpts::default();
+    opts.use_hosts_file = false;
+
+    Ok(TokioAsyncResolver::new(config, opts, connector))
+}
+
+/// TLS settings trusting the usual web roots plus the certificates in `ca_file`
+fn tls_client_config(ca_file: &Path) -> Result<ClientConfig, String> {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(ta.subject, ta.spki, ta.name_constraints)
+    }));
+
+    let file = File::open(ca_file)
+        .map_err(|e| format!("Failed to open {}: {}", ca_file.display(), e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|e| format!("Failed to read {}: {}", ca_file.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", ca_file.display()));
+    }
+
+    for cert in certs {
+        roots
+            .add(&Certificate(cert))
+            .map_err(|e| format!("Invalid certificate in {}: {}", ca_file.display(), e))?;
+    }
+
+    Ok(ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+/// Estimates the connection setup cost of an encrypted transport.
+///
+/// The first query pays for the TCP and TLS handshakes, the second reuses
+/// the connection; the difference is the handshake. Both ask for root
+/// records so the real lookups are neither cached nor slowed down.
+async fn measure_handshake(resolver: &TokioAsyncResolver) -> u128 {
     let start = Instant::now();
+    let _ = resolver.lookup(Name::root(), RecordType::NS).await;
+    let first = start.elapsed().as_millis();
 
-    // A records
-    let ipv4_lookup = resolver.ipv4_lookup(domain).await;
-    let ipv4: Vec<String> = match ipv4_lookup {
-        Ok(lookup) => lookup.iter().map(|ip| ip.to_string()).collect(),
-        Err(_) => vec![],
+    let start = Instant::now();
+    let _ = resolver.lookup(Name::root(), RecordType::SOA).await;
+    let second = start.elapsed().as_millis();
+
+    first.saturating_sub(second)
+}
+
+/// Human-readable name of the resolver the options select
+fn resolver_label(options: &DnsOptions) -> String {
+    if options.nameservers.is_empty() {
+        return "system".into();
+    }
+
+    options
+        .nameservers
+        .iter()
+        .map(|nameserver| nameserver.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub async fn resolve_domain(domain: &str, options: &DnsOptions) -> Result<DnsResult, String> {
+    let resolver = build_resolver(options).await?;
+
+    let transport = options
+        .nameservers
+        .first()
+        .map(|nameserver| nameserver.transport)
+        .unwrap_or(Transport::Udp);
+
+    // A lookup reports one transport and one handshake cost; a comparison
+    // asks each nameserver on its own, so it can mix them
+    if let Some(other) = options.nameservers.iter().find(|nameserver| nameserver.transport != transport) {
+        return Err(format!(
+            "{} and {} nameservers cannot be mixed in one lookup; compare them with dns --compare",
+            transport.describe(),
+            other.transport.describe()
+        ));
+    }
+
+    let anchors = if options.dnssec {
+        Some(TrustAnchors::load(options.trust_anchor.as_deref())?)
+    } else {
+        None
+    };
+
+    let handshake_ms = if transport.is_encrypted() {
+        Some(measure_handshake(&resolver).await)
+    } else {
+        None
     };
 
-    // AAAA records
-    let ipv6_lookup = resolver.ipv6_lookup(domain).await;
-    let ipv6: Vec<String> = match ipv6_lookup {
-        Ok(lookup) => lookup.iter().map(|ip| ip.to_string()).collect(),
-        Err(_) => vec![],
+    // An IP literal has no addresses to look up; its PTR goes in `reverse`
+    let literal = domain.parse::<IpAddr>().ok();
+    if let (Some(ip), Some(family)) = (literal, options.family) {
+        if !family.matches(&ip) {
+            return Err(format!("{} is not an {} address", ip, family));
+        }
+    }
+
+    let mut record_types = match (literal, options.family) {
+        (Some(_), _) => vec![],
+        (None, Some(family)) => vec![family.record_type()],
+        (None, None) => vec![RecordType::A, RecordType::AAAA],
     };
+    for record_type in &options.record_types {
+        let address_type = matches!(record_type, RecordType::A | RecordType::AAAA);
+        let skip = (literal.is_some() && (address_type || *record_type == RecordType::PTR))
+            || (options.family.is_some() && address_type);
+        if !skip && !record_types.contains(record_type) {
+            record_types.push(*record_type);
+        }
+    }
+
+    // All types go out at once, so a slow AAAA path shows in its own timing
+    // instead of delaying everything after it
+    let start = Instant::now();
+
+    let handles: Vec<_> = record_types
+        .into_iter()
+        .map(|record_type| {
+            let resolver = resolver.clone();
+            let domain = domain.to_string();
+            let handle = tokio::spawn(async move { query(&resolver, &domain, record_type).await });
+            (record_type, handle)
+        })
+        .collect();
+
+    let mut answers: Vec<RecordSet> = Vec::new();
+    for (record_type, handle) in handles {
+        let set = handle.await.unwrap_or_else(|e| {
+            record_set_error(record_type, DnsStatus::Error, Some(format!("Lookup task failed: {}", e)), 0)
+        });
+        answers.push(set);
+    }
 
     let elapsed = start.elapsed().as_millis();
 
+    if let Some(anchors) = anchors {
+        validate_answers(domain, options, anchors, &mut answers).await;
+    }
+
+    let mut ipv4 = Vec::new();
+    let mut ipv6 = Vec::new();
+    match literal {
+        Some(IpAddr::V4(ip)) => ipv4.push(ip.to_string()),
+        Some(IpAddr::V6(ip)) => ipv6.push(ip.to_string()),
+        None => {}
+    }
+    for set in &answers {
+        for record in &set.records {
+            match record.record_type.as_str() {
+                "A" => ipv4.push(record.data.clone()),
+                "AAAA" => ipv6.push(record.data.clone()),
+                _ => {}
+            }
+        }
+    }
+
+    let reverse = reverse_lookups(&resolver, ipv4.iter().chain(&ipv6)).await;
+
     Ok(DnsResult {
+        resolver: resolver_label(options),
+        transport,
+        handshake_ms,
         ipv4,
         ipv6,
         lookup_ms: elapsed,
+        answers,
+        reverse,
     })
-}
\ No newline at end of file
+}
+
+/// Looks up the PTR names of every address at once, and checks that each
+/// name resolves back to its address (forward-confirmed reverse DNS).
+async fn reverse_lookups<'a>(
+    resolver: &TokioAsyncResolver,
+    ips: impl Iterator<Item = &'a String>,
+) -> Vec<ReverseLookup> {
+    let handles: Vec<_> = ips
+        .filter_map(|ip| ip.parse::<IpAddr>().ok())
+        .map(|ip| {
+            let resolver = resolver.clone();
+            tokio::spawn(async move { reverse_lookup(&resolver, ip).await })
+        })
+        .collect();
+
+    let mut lookups = Vec::new();
+    for handle in handles {
+        if let Ok(lookup) = handle.await {
+            lookups.push(lookup);
+        }
+    }
+    lookups
+}
+
+async fn reverse_lookup(resolver: &TokioAsyncResolver, ip: IpAddr) -> ReverseLookup {
+    let set = query(resolver, &ip.to_string(), RecordType::PTR).await;
+    let names: Vec<String> = set
+        .records
+        .iter()
+        .filter(|record| record.record_type == "PTR")
+        .map(|record| record.data.clone())
+        .collect();
+
+    let fcrdns = if names.is_empty() {
+        FcrDns::NoPtr
+    } else {
+        let mut fcrdns = FcrDns::Mismatch;
+        for name in &names {
+            // lookup_ip only falls back to AAAA when a name has no A
+            // records, so ask for the address family being confirmed
+            let confirmed = match ip {
+                IpAddr::V4(v4) => resolver
+                    .ipv4_lookup(name.as_str())
+                    .await
+                    .is_ok_and(|lookup| lookup.iter().any(|addr| addr.0 == v4)),
+                IpAddr::V6(v6) => resolver
+                    .ipv6_lookup(name.as_str())
+                    .await
+                    .is_ok_and(|lookup| lookup.iter().any(|addr| addr.0 == v6)),
+            };
+            if confirmed {
+                fcrdns = FcrDns::Confirmed;
+                break;
+            }
+        }
+        fcrdns
+    };
+
+    ReverseLookup {
+        ip: ip.to_string(),
+        status: set.status,
+        names,
+        fcrdns,
+        error: set.error,
+    }
+}
+
+/// Names addresses by their first PTR record, remembering every outcome
+/// (including no name at all) so each address is only asked about once.
+pub struct PtrCache {
+    resolver: TokioAsyncResolver,
+    names: HashMap<IpAddr, Option<String>>,
+}
+
+impl PtrCache {
+    pub async fn new(options: &DnsOptions) -> Result<Self, String> {
+        Ok(Self {
+            resolver: build_resolver(options).await?,
+            names: HashMap::new(),
+        })
+    }
+
+    /// Looks up every address not seen before at once, giving up on each
+    /// after `timeout`.
+    pub async fn resolve(&mut self, ips: impl IntoIterator<Item = IpAddr>, timeout: Duration) {
+        let mut handles = Vec::new();
+        for ip in ips {
+            if self.names.contains_key(&ip) {
+                continue;
+            }
+            // Claims the address so duplicates in `ips` share one lookup
+            self.names.insert(ip, None);
+
+            let resolver = self.resolver.clone();
+            handles.push((ip, tokio::spawn(async move {
+                let lookup = tokio::time::timeout(timeout, resolver.reverse_lookup(ip)).await;
+                lookup.ok()?.ok()?.iter().next().map(|name| name.to_utf8().trim_end_matches('.').to_string())
+            })));
+        }
+
+        for (ip, handle) in handles {
+            if let Ok(name) = handle.await {
+                self.names.insert(ip, name);
+            }
+        }
+    }
+
+    /// The name found for `ip`, if it has been resolved and has one
+    pub fn name(&self, ip: &IpAddr) -> Option<&str> {
+        self.names.get(ip)?.as_deref()
+    }
+}
+
+/// Runs DNSSEC validation for every record set, through the first plain
+/// UDP/TCP nameserver (or the system's), with checking disabled so bogus
+/// data still comes back to be diagnosed.
+async fn validate_answers(domain: &str, options: &DnsOptions, anchors: TrustAnchors, answers: &mut [RecordSet]) {
+    let server = match plain_server(options) {
+        Ok(server) => server,
+        Err(e) => {
+            for set in answers.iter_mut() {
+                set.dnssec = Some(DnssecResult::indeterminate(e.clone()));
+            }
+            return;
+        }
+    };
+
+    let mut validator = Validator::new(server, anchors);
+    for set in answers.iter_mut() {
+        let Ok(record_type) = RecordType::from_str(&set.record_type) else {
+            continue;
+        };
+        let result = match query_name(domain, record_type) {
+            Ok(name) => validator.validate(&name, record_type).await,
+            Err(e) => DnssecResult::indeterminate(e),
+        };
+        set.dnssec = Some(result);
+    }
+}
+
+/// Where hand-built queries go: the first plain nameserver, else the system's
+fn plain_server(options: &DnsOptions) -> Result<RawServer, String> {
+    if !options.nameservers.is_empty() {
+        return options
+            .nameservers
+            .iter()
+            .find_map(Nameserver::raw_server)
+            .ok_or_else(|| "DNSSEC validation needs a plain UDP or TCP --resolver".to_string());
+    }
+
+    let (config, _) = read_system_conf().map_err(|e| format!("Resolver init failed: {}", e))?;
+    config
+        .name_servers()
+        .first()
+        .map(|nameserver| RawServer {
+            addr: nameserver.socket_addr,
+            tcp: nameserver.protocol == Protocol::Tcp,
+        })
+        .ok_or_else(|| "No system nameserver configured".to_string())
+}
+
+/// A nameserver for hand-built queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawServer {
+    pub addr: SocketAddr,
+    pub tcp: bool,
+}
+
+/// Header and EDNS flags for a hand-built query
+#[derive(Debug, Clone, Copy)]
+pub struct QueryFlags {
+    pub recursion_desired: bool,
+    pub dnssec_ok: bool,
+    pub checking_disabled: bool,
+    pub timeout: Duration,
+}
+
+/// Sends one query straight to `server`, bypassing the resolver and its
+/// cache, and retries over TCP when the UDP answer is truncated.
+pub async fn raw_query(
+    server: RawServer,
+    name: &Name,
+    record_type: RecordType,
+    flags: QueryFlags,
+) -> Result<Message, String> {
+    let mut message = Message::new();
+    message
+        .set_id(rand::random())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(flags.recursion_desired)
+        .set_checking_disabled(flags.checking_disabled)
+        .add_query(Query::query(name.clone(), record_type));
+
+    let mut edns = Edns::new();
+    edns.set_max_payload(4096).set_dnssec_ok(flags.dnssec_ok);
+    message.set_edns(edns);
+
+    let request = message
+        .to_vec()
+        .map_err(|e| format!("Failed to encode query: {}", e))?;
+
+    let exchange = async {
+        if !server.tcp {
+            let response = udp_exchange(server.addr, &request).await?;
+            let response = Message::from_vec(&response)
+                .map_err(|e| format!("Malformed response from {}: {}", server.addr, e))?;
+            if !response.truncated() {
+                return Ok(response);
+            }
+        }
+
+        let response = tcp_exchange(server.addr, &request).await?;
+        Message::from_vec(&response).map_err(|e| format!("Malformed response from {}: {}", server.addr, e))
+    };
+
+    let response = tokio::time::timeout(flags.timeout, exchange)
+        .await
+        .map_err(|_| format!("No response from {}", server.addr))??;
+
+    if response.id() != message.id() {
+        return Err(format!("Mismatched response id from {}", server.addr));
+    }
+
+    Ok(response)
+}
+
+async fn udp_exchange(server: SocketAddr, request: &[u8]) -> Result<Vec<u8>, String> {
+    let bind: SocketAddr = if server.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+
+    let socket = UdpSocket::bind(bind)
+        .await
+        .map_err(|e| format!("Socket error: {}", e))?;
+    socket
+        .connect(server)
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", server, e))?;
+    socket
+        .send(request)
+        .await
+        .map_err(|e| format!("Send to {} failed: {}", server, e))?;
+
+    let mut buf = vec![0u8; 65535];
+    let len = socket
+        .recv(&mut buf)
+        .await
+        .map_err(|e| format!("Receive from {} failed: {}", server, e))?;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+async fn tcp_exchange(server: SocketAddr, request: &[u8]) -> Result<Vec<u8>, String> {
+    let mut stream = TcpStream::connect(server)
+        .await
+        .map_err(|e| format!("Failed to reach {}: {}", server, e))?;
+
+    // DNS over TCP prefixes every message with its length
+    let mut framed = (request.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(request);
+    stream
+        .write_all(&framed)
+        .await
+        .map_err(|e| format!("Send to {} failed: {}", server, e))?;
+
+    let mut len = [0u8; 2];
+    stream
+        .read_exact(&mut len)
+        .await
+        .map_err(|e| format!("Receive from {} failed: {}", server, e))?;
+    let mut buf = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream
+        .read_exact(&mut buf)
+        .await
+        .map_err(|e| format!("Receive from {} failed: {}", server, e))?;
+    Ok(buf)
+}
+
+/// The same lookup run against several resolvers
+#[derive(Serialize, Debug, Clone)]
+pub struct DnsComparison {
+    pub rows: Vec<ComparisonRow>,
+    /// Record types whose answers differ between the resolvers that answered
+    pub disagreements: Vec<String>,
+    /// Resolvers that failed every query, left out of the comparison
+    pub failed: Vec<String>,
+}
+
+/// One resolver's answer in a [`DnsComparison`]
+#[derive(Serialize, Debug, Clone)]
+pub struct ComparisonRow {
+    pub resolver: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<DnsResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Resolves `domain` against every configured nameserver concurrently.
+pub async fn compare_resolvers(domain: &str, options: &DnsOptions) -> Result<DnsComparison, String> {
+    if options.nameservers.len() < 2 {
+        return Err("Comparing resolvers needs at least two --resolver addresses".into());
+    }
+
+    let handles: Vec<_> = options
+        .nameservers
+        .iter()
+        .map(|nameserver| {
+            let domain = domain.to_string();
+            let options = DnsOptions {
+                nameservers: vec![nameserver.clone()],
+                ..options.clone()
+            };
+            tokio::spawn(async move { resolve_domain(&domain, &options).await })
+        })
+        .collect();
+
+    let mut rows = Vec::new();
+    for (nameserver, handle) in options.nameservers.iter().zip(handles) {
+        let result = handle
+            .await
+            .unwrap_or_else(|e| Err(format!("Lookup task failed: {}", e)));
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(e) => (None, Some(e)),
+        };
+        rows.push(ComparisonRow {
+            resolver: nameserver.to_string(),
+            result,
+            error,
+        });
+    }
+
+    let disagreements = find_disagreements(&rows);
+    let failed = failed_resolvers(&rows);
+
+    Ok(DnsComparison { rows, disagreements, failed })
+}
+
+/// Resolvers whose lookup failed or that answered none of the queries
+fn failed_resolvers(rows: &[ComparisonRow]) -> Vec<String> {
+    rows.iter()
+        .filter(|row| {
+            row.result
+                .as_ref()
+                .is_none_or(|result| !result.answers.iter().any(|set| set.status.answered()))
+        })
+        .map(|row| row.resolver.clone())
+        .collect()
+}
+
+/// Record types for which the resolvers that answered returned different data
+/// (TTLs are ignored). Every type any of them answered is compared; failed
+/// queries are left out rather than counted as empty answers.
+fn find_disagreements(rows: &[ComparisonRow]) -> Vec<String> {
+    let results: Vec<&DnsResult> = rows.iter().filter_map(|row| row.result.as_ref()).collect();
+
+    let mut record_types: Vec<&String> = Vec::new();
+    for set in results.iter().flat_map(|result| &result.answers) {
+        if !record_types.contains(&&set.record_type) {
+            record_types.push(&set.record_type);
+        }
+    }
+
+    record_types
+        .into_iter()
+        .filter(|record_type| {
+            let answers: Vec<Vec<String>> = results
+                .iter()
+                .filter_map(|result| match result.answers.iter().find(|set| &set.record_type == *record_type) {
+                    Some(set) if set.status.answered() => Some(set.sorted_data()),
+                    Some(_) => None,
+                    None => Some(Vec::new()),
+                })
+                .collect();
+            answers.windows(2).any(|pair| pair[0] != pair[1])
+        })
+        .cloned()
+        .collect()
+}
+
+impl RecordSet {
+    /// The record data of this set, sorted so answers can be compared
+    pub fn sorted_data(&self) -> Vec<String> {
+        let mut data: Vec<String> = self
+            .records
+            .iter()
+            .map(|record| {
+                if record.record_type == self.record_type {
+                    record.data.clone()
+                } else {
+                    format!("{} {}", record.record_type, record.data)
+                }
+            })
+            .collect();
+        data.sort();
+        data
+    }
+}
+
+/// The name to ask about; PTR queries for an IP literal go to its
+/// in-addr.arpa / ip6.arpa name
+fn query_name(domain: &str, record_type: RecordType) -> Result<Name, String> {
+    match (record_type, domain.parse::<IpAddr>()) {
+        (RecordType::PTR, Ok(ip)) => Ok(Name::from(ip)),
+        _ => Name::from_utf8(domain).map_err(|e| format!("Invalid name: {}", e)),
+    }
+}
+
+/// Runs one query and collects its records, CNAMEs along the way included.
+async fn query(resolver: &TokioAsyncResolver, domain: &str, record_type: RecordType) -> RecordSet {
+    let name = match query_name(domain, record_type) {
+        Ok(name) => name,
+        Err(e) => return record_set_error(record_type, DnsStatus::Error, Some(e), 0),
+    };
+
+    let start = Instant::now();
+    let result = resolver.lookup(name, record_type).await;
+    let lookup_ms = start.elapsed().as_millis();
+
+    match result {
+        Ok(lookup) => {
+            let records: Vec<DnsRecord> = lookup.record_iter().filter_map(DnsRecord::from_record).collect();
+            RecordSet {
+                record_type: record_type.to_string(),
+                status: DnsStatus::NoError,
+                ttl: records.iter().map(|record| record.ttl).min(),
+                records,
+                lookup_ms,
+                error: None,
+                dnssec: None,
+            }
+        }
+        Err(e) => {
+            let status = DnsStatus::from_error(&e);
+            // NXDOMAIN and NODATA are answers, not failures; keep the raw
+            // message only for the rest
+            let error = match status {
+                DnsStatus::NxDomain | DnsStatus::NoData => None,
+                _ => Some(e.to_string()),
+            };
+            record_set_error(record_type, status, error, lookup_ms)
+        }
+    }
+}
+
+fn record_set_error(record_type: RecordType, status: DnsStatus, error: Option<String>, lookup_ms: u128) -> RecordSet {
+    RecordSet {
+        record_type: record_type.to_string(),
+        status,
+        records: vec![],
+        ttl: None,
+        lookup_ms,
+        error,
+        dnssec: None,
+    }
+}
+
+/// Presentation form of a record's data
+fn format_rdata(data: &RData) -> String {
+    match data {
+        RData::A(ip) => ip.to_string(),
+        RData::AAAA(ip) => ip.to_string(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, StubDns};
+
+    fn nameserver(host: &str, port: u16, transport: Transport) -> Nameserver {
+        Nameserver { host: host.into(), port, transport }
+    }
+
+    #[test]
+    fn parses_nameserver_forms() {
+        let parse = |value: &str| value.parse::<Nameserver>();
+
+        assert_eq!(parse("9.9.9.9"), Ok(nameserver("9.9.9.9", 53, Transport::Udp)));
+        assert_eq!(parse("127.0.0.1:5353"), Ok(nameserver("127.0.0.1", 5353, Transport::Udp)));
+        assert_eq!(parse("2620:fe::fe"), Ok(nameserver("2620:fe::fe", 53, Transport::Udp)));
+        assert_eq!(parse("[2620:fe::fe]:5353"), Ok(nameserver("2620:fe::fe", 5353, Transport::Udp)));
+        assert_eq!(parse("[::1]"), Ok(nameserver("::1", 53, Transport::Udp)));
+        assert_eq!(parse("tcp://1.1.1.1"), Ok(nameserver("1.1.1.1", 53, Transport::Tcp)));
+        assert_eq!(parse("tcp://[::1]:5353"), Ok(nameserver("::1", 5353, Transport::Tcp)));
+    }
+
+    #[test]
+    fn rejects_bad_nameservers() {
+        let parse = |value: &str| value.parse::<Nameserver>();
+
+        assert!(parse("ftp://1.1.1.1").unwrap_err().contains("unsupported nameserver scheme"));
+        assert!(parse("dns.google").unwrap_err().contains("must be an IP address"));
+        assert!(parse("1.1.1.1:").is_err());
+        assert!(parse("1.1.1.1:dns").is_err());
+        assert!(parse("[::1]5353").is_err());
+        assert!(parse(":53").is_err());
+        assert!(parse("").is_err());
+        assert!(parse("1.1.1.1/dns-query").unwrap_err().contains("unsupported nameserver path"));
+    }
+
+    #[test]
+    fn parses_encrypted_nameservers() {
+        assert_eq!(
+            Nameserver::parse_doh("https://dns.google/dns-query"),
+            Ok(nameserver("dns.google", 443, Transport::Https))
+        );
+        assert_eq!(Nameserver::parse_doh("dns.google:8443"), Ok(nameserver("dns.google", 8443, Transport::Https)));
+        assert!(Nameserver::parse_doh("https://dns.google/resolve").unwrap_err().contains("only /dns-query"));
+        assert!(Nameserver::parse_doh("tls://dns.google").is_err());
+
+        assert_eq!(Nameserver::parse_dot("dns.quad9.net"), Ok(nameserver("dns.quad9.net", 853, Transport::Tls)));
+        assert_eq!(Nameserver::parse_dot("[2620:fe::fe]:8853"), Ok(nameserver("2620:fe::fe", 8853, Transport::Tls)));
+        assert!(Nameserver::parse_dot("https://dns.quad9.net").is_err());
+        assert!(Nameserver::parse_dot("dns.quad9.net/dns-query").is_err());
+    }
+
+    #[test]
+    fn nameservers_print_as_they_parse() {
+        for value in ["127.0.0.1:5353", "[::1]:53", "tcp://1.1.1.1:53", "tls://dns.quad9.net:853"] {
+            let nameserver: Nameserver = if value.starts_with("tls://") {
+                Nameserver::parse_dot(value).unwrap()
+            } else {
+                value.parse().unwrap()
+            };
+            assert_eq!(nameserver.to_string(), value);
+        }
+    }
+
+    async fn stub_server() -> StubDns {
+        StubDns::start(stub_handler).await
+    }
+
+    /// Answers host.example with one address of each family, and names
+    /// 192.0.2.10 back to it
+    fn stub_handler(request: &Message) -> Message {
+        let query = &request.queries()[0];
+        let mut response = testing::reply(request, ResponseCode::NoError);
+        match (query.name().to_ascii().as_str(), query.query_type()) {
+            ("host.example.", RecordType::A) => {
+                response.add_answer(testing::a("host.example.", Ipv4Addr::new(192, 0, 2, 10)));
+            }
+            ("host.example.", RecordType::AAAA) => {
+                response.add_answer(testing::aaaa("host.example.", "2001:db8::10".parse().unwrap()));
+            }
+            ("10.2.0.192.in-addr.arpa.", RecordType::PTR) => {
+                response.add_answer(testing::ptr("10.2.0.192.in-addr.arpa.", "host.example."));
+            }
+            ("host.example.", _) => {}
+            _ => {
+                response.set_response_code(ResponseCode::NXDomain);
+            }
+        }
+        response
+    }
+
+    #[tokio::test]
+    async fn resolves_through_a_given_nameserver() {
+        for transport in [Transport::Udp, Transport::Tcp] {
+            let stub = stub_server().await;
+            let options = DnsOptions {
+                nameservers: vec![nameserver("127.0.0.1", stub.addr.port(), transport)],
+                record_types: vec![RecordType::MX],
+                ..DnsOptions::default()
+            };
+
+            let result = resolve_domain("host.example", &options).await.unwrap();
+            assert_eq!(result.transport, transport);
+            assert_eq!(result.ipv4, vec!["192.0.2.10"]);
+            assert_eq!(result.ipv6, vec!["2001:db8::10"]);
+
+            let mx = result.answers.iter().find(|set| set.record_type == "MX").unwrap();
+            assert_eq!(mx.status, DnsStatus::NoData);
+
+            let reverse = &result.reverse[0];
+            assert_eq!(reverse.names, vec!["host.example."]);
+            assert_eq!(reverse.fcrdns, FcrDns::Confirmed);
+            // 2001:db8::10 has no PTR
+            assert_eq!(result.reverse[1].fcrdns, FcrDns::NoPtr);
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_nxdomain_from_a_given_nameserver() {
+        let stub = stub_server().await;
+        let options = DnsOptions {
+            nameservers: vec![nameserver("127.0.0.1", stub.addr.port(), Transport::Udp)],
+            ..DnsOptions::default()
+        };
+
+        let result = resolve_domain("missing.example", &options).await.unwrap();
+        assert!(result.ipv4.is_empty());
+        assert!(result.answers.iter().all(|set| set.status == DnsStatus::NxDomain));
+    }
+
+    #[tokio::test]
+    async fn resolves_over_tls_and_https() {
+        for transport in [Transport::Tls, Transport::Https] {
+            let stub = match transport {
+                Transport::Tls => StubDns::start_tls(stub_handler).await,
+                _ => StubDns::start_https(stub_handler).await,
+            };
+            let options = DnsOptions {
+                nameservers: vec![nameserver("127.0.0.1", stub.addr.port(), transport)],
+                ca_file: Some(testing::CA_FILE.into()),
+                ..DnsOptions::default()
+            };
+
+            let result = resolve_domain("host.example", &options).await.unwrap();
+            assert_eq!(result.transport, transport);
+            assert!(result.handshake_ms.is_some());
+            assert_eq!(result.ipv4, vec!["192.0.2.10"]);
+            assert_eq!(result.reverse[0].fcrdns, FcrDns::Confirmed);
+        }
+    }
+
+    #[tokio::test]
+    async fn untrusted_certificates_fail_the_lookup() {
+        let stub = StubDns::start_tls(stub_handler).await;
+        let options = DnsOptions {
+            nameservers: vec![nameserver("127.0.0.1", stub.addr.port(), Transport::Tls)],
+            ..DnsOptions::default()
+        };
+
+        let result = resolve_domain("host.example", &options).await.unwrap();
+        assert!(result.ipv4.is_empty());
+        assert!(result.answers.iter().all(|set| set.status != DnsStatus::NoError));
+    }
+
+    #[tokio::test]
+    async fn rejects_mixed_transports() {
+        let options = DnsOptions {
+            nameservers: vec![
+                nameserver("127.0.0.1", 53, Transport::Udp),
+                nameserver("127.0.0.1", 853, Transport::Tls),
+            ],
+            ..DnsOptions::default()
+        };
+
+        let error = resolve_domain("host.example", &options).await.unwrap_err();
+        assert!(error.contains("cannot be mixed"));
+    }
+
+    fn row(resolver: &str, answers: &[(&str, &[&str])]) -> ComparisonRow {
+        let answers = answers
+            .iter()
+            .map(|(record_type, data)| RecordSet {
+                record_type: record_type.to_string(),
+                status: DnsStatus::NoError,
+                records: data
+                    .iter()
+                    .map(|data| DnsRecord {
+                        name: "host.example.".into(),
+                        record_type: record_type.to_string(),
+                        ttl: 300,
+                        data: data.to_string(),
+                    })
+                    .collect(),
+                ttl: Some(300),
+                lookup_ms: 0,
+                error: None,
+                dnssec: None,
+            })
+            .collect();
+        let result = DnsResult {
+            resolver: resolver.into(),
+            transport: Transport::Udp,
+            handshake_ms: None,
+            ipv4: Vec::new(),
+            ipv6: Vec::new(),
+            lookup_ms: 0,
+            answers,
+            reverse: Vec::new(),
+        };
+        ComparisonRow { resolver: resolver.into(), result: Some(result), error: None }
+    }
+
+    #[test]
+    fn disagreements_cover_every_answered_type() {
+        let rows = [
+            row("9.9.9.9", &[("A", &["192.0.2.1"])]),
+            row("1.1.1.1", &[("A", &["192.0.2.1"]), ("AAAA", &["2001:db8::1"])]),
+            row("8.8.8.8", &[("A", &["192.0.2.1"]), ("AAAA", &["2001:db8::1"]), ("MX", &["10 mx.example."])]),
+        ];
+        assert_eq!(find_disagreements(&rows), ["AAAA", "MX"]);
+
+        let rows = [
+            row("9.9.9.9", &[("A", &["192.0.2.1", "192.0.2.2"])]),
+            row("1.1.1.1", &[("A", &["192.0.2.2", "192.0.2.1"])]),
+        ];
+        assert!(find_disagreements(&rows).is_empty());
+    }
+
+    #[test]
+    fn failed_resolvers_do_not_disagree() {
+        let errored = ComparisonRow { resolver: "192.0.2.53".into(), result: None, error: Some("bad name".into()) };
+        // Marks every record set from `from` on as timed out
+        let time_out = |mut row: ComparisonRow, from: usize| {
+            for set in &mut row.result.as_mut().unwrap().answers[from..] {
+                set.status = DnsStatus::Timeout;
+            }
+            row
+        };
+        let rows = [
+            errored,
+            time_out(row("192.0.2.54", &[("A", &[]), ("AAAA", &[])]), 0),
+            time_out(row("8.8.8.8", &[("A", &["192.0.2.1"]), ("AAAA", &[])]), 1),
+            row("9.9.9.9", &[("A", &["192.0.2.1"]), ("AAAA", &["2001:db8::1"])]),
+            row("1.1.1.1", &[("A", &["192.0.2.1"]), ("AAAA", &["2001:db8::1"])]),
+        ];
+        assert!(find_disagreements(&rows).is_empty());
+        assert_eq!(failed_resolvers(&rows), ["192.0.2.53", "192.0.2.54"]);
+    }
+}
@@ -0,0 +1,1191 @@
+// StaxPing — Unified Network Diagnostics
+// Copyright (c) 2026 StaxDash
+//
+// This source code is provided under the StaxPing Source‑Available License & EULA.
+// You may view, modify, and redistribute this code for personal or internal use.
+// Commercial use of any kind requires explicit written permission from StaxDash.
+//
+// Full license text available in LICENSE and EULA.md.
+
+use trust_dns_resolver::{
+    proto::{
+        op::{Message, ResponseCode},
+        rr::{
+            dnssec::{
+                rdata::{DNSSECRData, DNSKEY, DS, NSEC, NSEC3, RRSIG},
+                Algorithm, DigestType, PublicKeyBuf, TrustAnchor, Verifier,
+            },
+            DNSClass, RData, Record, RecordType,
+        },
+    },
+    Name,
+};
+use data_encoding::{BASE32_DNSSEC, BASE64, HEXUPPER_PERMISSIVE};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::dns::{raw_query, QueryFlags, RawServer};
+
+/// Every validation query asks for signatures and turns checking off, so
+/// the upstream resolver hands back bogus data instead of a bare SERVFAIL.
+const FLAGS: QueryFlags = QueryFlags {
+    recursion_desired: true,
+    dnssec_ok: true,
+    checking_disabled: true,
+    timeout: Duration::from_secs(3),
+};
+
+/// KSK-2024, the root key that follows KSK-2017 (20326). trust-dns only ships
+/// the older keys, so this one is anchored by the DS record IANA publishes.
+const ROOT_KSK_2024_DS: &str = "38696 8 2 683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16";
+
+/// Outcome of validating one record set, in order of increasing severity
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum DnssecStatus {
+    /// Signed, with an unbroken chain of trust up to the root
+    Secure,
+    /// Provably unsigned: some zone on the way is delegated without a DS
+    Insecure,
+    /// Validation could not run, e.g. the resolver did not answer
+    Indeterminate,
+    /// Signatures are missing, expired or do not verify
+    Bogus,
+}
+
+impl fmt::Display for DnssecStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            DnssecStatus::Secure => "secure",
+            DnssecStatus::Insecure => "insecure",
+            DnssecStatus::Indeterminate => "indeterminate",
+            DnssecStatus::Bogus => "bogus",
+        };
+        f.write_str(label)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DnssecResult {
+    pub status: DnssecStatus,
+    /// Which link of the chain decided the status, for everything but secure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl DnssecResult {
+    fn secure() -> Self {
+        Self { status: DnssecStatus::Secure, detail: None }
+    }
+
+    pub fn indeterminate(detail: impl Into<String>) -> Self {
+        Self { status: DnssecStatus::Indeterminate, detail: Some(detail.into()) }
+    }
+
+    /// Keeps whichever of the two results is worse.
+    fn worst(self, other: Self) -> Self {
+        if other.status > self.status {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+/// Root keys every chain of trust starts from, given either as the DNSKEY
+/// itself or as a DS digest of it
+pub struct TrustAnchors {
+    keys: TrustAnchor,
+    ds: Vec<DS>,
+}
+
+impl Default for TrustAnchors {
+    /// KSK-2010, KSK-2017 and KSK-2024
+    fn default() -> Self {
+        Self {
+            keys: TrustAnchor::default(),
+            ds: vec![parse_ds(ROOT_KSK_2024_DS).expect("built-in root DS")],
+        }
+    }
+}
+
+impl TrustAnchors {
+    /// The built-in root anchors, or those in `path` instead of them.
+    pub fn load(path: Option<&Path>) -> Result<Self, String> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read trust anchor {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Reads root DS and DNSKEY records in zone-file form, one per line
+    /// (". 172800 IN DS 20326 8 2 E06D..."), as in IANA's root-anchors or
+    /// unbound's root.key.
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut anchors = Self { keys: TrustAnchor::new(), ds: Vec::new() };
+
+        for line in text.lines() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let Some(at) = fields
+                .iter()
+                .position(|field| field.eq_ignore_ascii_case("DS") || field.eq_ignore_ascii_case("DNSKEY"))
+            else {
+                if fields.is_empty() {
+                    continue;
+                }
+                return Err(format!("expected a DS or DNSKEY record, got '{}'", line));
+            };
+
+            if let Some(owner) = fields.first().filter(|_| at > 0) {
+                if *owner != "." {
+                    return Err(format!("trust anchor for '{}' is not for the root zone", owner));
+                }
+            }
+
+            let rdata = fields[at + 1..].join(" ");
+            if fields[at].eq_ignore_ascii_case("DS") {
+                anchors.ds.push(parse_ds(&rdata)?);
+            } else {
+                anchors.keys.insert_trust_anchor(&PublicKeyBuf::new(parse_dnskey(&rdata)?.public_key().to_vec()));
+            }
+        }
+
+        if anchors.keys.is_empty() && anchors.ds.is_empty() {
+            return Err("no DS or DNSKEY records found".into());
+        }
+        Ok(anchors)
+    }
+
+    fn contains(&self, key: &DNSKEY) -> bool {
+        self.keys.contains_dnskey_bytes(key.public_key())
+            || self.ds.iter().any(|ds| ds.covers(&Name::root(), key).unwrap_or(false))
+    }
+}
+
+/// "KEYTAG ALGORITHM DIGESTTYPE HEX"
+fn parse_ds(rdata: &str) -> Result<DS, String> {
+    let invalid = || format!("invalid DS record '{}'", rdata);
+    let fields: Vec<&str> = rdata.split_whitespace().collect();
+    let [tag, algorithm, digest_type, digest @ ..] = fields.as_slice() else {
+        return Err(invalid());
+    };
+
+    let digest = HEXUPPER_PERMISSIVE.decode(digest.concat().as_bytes()).map_err(|_| invalid())?;
+    if digest.is_empty() {
+        return Err(invalid());
+    }
+    Ok(DS::new(
+        tag.parse().map_err(|_| invalid())?,
+        Algorithm::from_u8(algorithm.parse().map_err(|_| invalid())?),
+        DigestType::from_u8(digest_type.parse().map_err(|_| invalid())?).map_err(|_| invalid())?,
+        digest,
+    ))
+}
+
+/// "FLAGS PROTOCOL ALGORITHM BASE64"
+fn parse_dnskey(rdata: &str) -> Result<DNSKEY, String> {
+    let invalid = || format!("invalid DNSKEY record '{}'", rdata);
+    let fields: Vec<&str> = rdata.split_whitespace().collect();
+    let [flags, "3", algorithm, key @ ..] = fields.as_slice() else {
+        return Err(invalid());
+    };
+
+    let flags: u16 = flags.parse().map_err(|_| invalid())?;
+    let key = BASE64.decode(key.concat().as_bytes()).map_err(|_| invalid())?;
+    if key.is_empty() {
+        return Err(invalid());
+    }
+    Ok(DNSKEY::new(
+        flags & 0x0100 != 0,
+        flags & 0x0001 != 0,
+        flags & 0x0080 != 0,
+        Algorithm::from_u8(algorithm.parse().map_err(|_| invalid())?),
+        key,
+    ))
+}
+
+/// How far the chain of trust reaches for a given name
+#[derive(Debug, Clone)]
+enum Chain {
+    /// `zone` is the deepest signed zone and `keys` its validated DNSKEYs
+    Secure { zone: Name, keys: Vec<DNSKEY> },
+    Insecure(String),
+    Bogus(String),
+    Indeterminate(String),
+}
+
+impl Chain {
+    fn result(&self) -> DnssecResult {
+        let (status, detail) = match self {
+            Chain::Secure { .. } => return DnssecResult::secure(),
+            Chain::Insecure(detail) => (DnssecStatus::Insecure, detail),
+            Chain::Bogus(detail) => (DnssecStatus::Bogus, detail),
+            Chain::Indeterminate(detail) => (DnssecStatus::Indeterminate, detail),
+        };
+        DnssecResult { status, detail: Some(detail.clone()) }
+    }
+}
+
+/// Walks the chain of trust from the root trust anchor down to each name,
+/// caching every zone it has already checked.
+///
+/// Denials of existence must be signed, and their NSEC or NSEC3 records must
+/// cover the denied name or show the denied type missing from it.
+pub struct Validator {
+    server: RawServer,
+    anchors: TrustAnchors,
+    chains: HashMap<Name, Chain>,
+}
+
+impl Validator {
+    pub fn new(server: RawServer, anchors: TrustAnchors) -> Self {
+        Self {
+            server,
+            anchors,
+            chains: HashMap::new(),
+        }
+    }
+
+    /// Validates the answer for `name`/`record_type`, CNAMEs included.
+    pub async fn validate(&mut self, name: &Name, record_type: RecordType) -> DnssecResult {
+        let mut name = name.clone();
+        name.set_fqdn(true);
+
+        let response = match raw_query(self.server, &name, record_type, FLAGS).await {
+            Ok(response) => response,
+            Err(e) => return DnssecResult::indeterminate(e),
+        };
+
+        match response.response_code() {
+            ResponseCode::NoError | ResponseCode::NXDomain => {}
+            code => return DnssecResult::indeterminate(format!("resolver answered {}", code)),
+        }
+
+        if response.answers().is_empty() {
+            return self.validate_denial(&name, record_type, &response).await;
+        }
+
+        let mut result = DnssecResult::secure();
+        for (owner, covered, records) in rrsets(response.answers()) {
+            let sigs = signatures(response.answers(), &owner, covered);
+            result = result.worst(self.validate_rrset(&owner, covered, &records, &sigs).await);
+        }
+        result
+    }
+
+    /// An empty answer from a signed zone must come with a signed SOA and
+    /// NSEC/NSEC3 records in the authority section that prove the name (or
+    /// the type at it) missing.
+    async fn validate_denial(&mut self, name: &Name, record_type: RecordType, response: &Message) -> DnssecResult {
+        let authority = response.name_servers();
+        let sets = rrsets(authority);
+
+        if sets.is_empty() {
+            return match self.chain_for(name).await {
+                Chain::Secure { zone, .. } => DnssecResult {
+                    status: DnssecStatus::Bogus,
+                    detail: Some(format!("unsigned denial of {} {} in {}", name, record_type, zone)),
+                },
+                chain => chain.result(),
+            };
+        }
+
+        let mut result = DnssecResult::secure();
+        for (owner, covered, records) in sets {
+            let sigs = signatures(authority, &owner, covered);
+            result = result.worst(self.validate_rrset(&owner, covered, &records, &sigs).await);
+        }
+        if result.status != DnssecStatus::Secure {
+            return result;
+        }
+
+        // The signatures hold; now the records have to deny the right thing
+        let zone = authority.iter().find_map(|record| match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::RRSIG(sig)))
+                if matches!(sig.type_covered(), RecordType::NSEC | RecordType::NSEC3) =>
+            {
+                Some(sig.signer_name().clone())
+            }
+            _ => None,
+        });
+        let denied = match response.response_code() {
+            ResponseCode::NXDomain => None,
+            _ => Some(record_type),
+        };
+        match zone {
+            Some(zone) if Denial::from_records(authority).denies(&zone, name, denied) => result,
+            _ => match denied {
+                Some(record_type) => bogus(format!("no NSEC or NSEC3 record proves that {} has no {}", name, record_type)),
+                None => bogus(format!("no NSEC or NSEC3 record proves that {} does not exist", name)),
+            },
+        }
+    }
+
+    /// Checks one RRset against the keys of the zone that signed it.
+    async fn validate_rrset(
+        &mut self,
+        owner: &Name,
+        record_type: RecordType,
+        records: &[Record],
+        sigs: &[RRSIG],
+    ) -> DnssecResult {
+        let Some(signer) = sigs.first().map(|sig| sig.signer_name().clone()) else {
+            // Unsigned data is only fine outside the signed part of the tree
+            return match self.chain_for(owner).await {
+                Chain::Secure { zone, .. } => DnssecResult {
+                    status: DnssecStatus::Bogus,
+                    detail: Some(format!("missing RRSIG for {} {} in signed zone {}", owner, record_type, zone)),
+                },
+                chain => chain.result(),
+            };
+        };
+
+        if !signer.zone_of(owner) {
+            return bogus(format!("{} {} is signed by unrelated zone {}", owner, record_type, signer));
+        }
+
+        match self.chain_for(&signer).await {
+            Chain::Secure { zone, keys } if zone == signer => {
+                match verify_rrset(&keys, owner, record_type, records, sigs) {
+                    Ok(()) => DnssecResult::secure(),
+                    Err(e) => bogus(e),
+                }
+            }
+            Chain::Secure { .. } => bogus(format!("{} {} is signed by {}, which is not a zone", owner, record_type, signer)),
+            chain => chain.result(),
+        }
+    }
+
+    /// Follows the delegations from the root down to `name`.
+    async fn chain_for(&mut self, name: &Name) -> Chain {
+        let mut chain = match self.chains.get(&Name::root()) {
+            Some(chain) => chain.clone(),
+            None => {
+                let chain = self.root_chain().await;
+                self.chains.insert(Name::root(), chain.clone());
+                chain
+            }
+        };
+
+        for labels in 1..=name.num_labels() as usize {
+            if !matches!(chain, Chain::Secure { .. }) {
+                break;
+            }
+
+            let child = name.trim_to(labels);
+            if let Some(cached) = self.chains.get(&child) {
+                chain = cached.clone();
+                continue;
+            }
+
+            let Step { chain: next, done } = self.delegation(chain, &child).await;
+            chain = next;
+            self.chains.insert(child, chain.clone());
+            if done {
+                break;
+            }
+        }
+
+        chain
+    }
+
+    /// The root DNSKEY RRset must be signed by a key in the trust anchor.
+    async fn root_chain(&self) -> Chain {
+        let root = Name::root();
+        let response = match raw_query(self.server, &root, RecordType::DNSKEY, FLAGS).await {
+            Ok(response) => response,
+            Err(e) => return Chain::Indeterminate(e),
+        };
+
+        let keys = dnskeys(response.answers(), &root);
+        if keys.is_empty() {
+            return Chain::Bogus("resolver returned no root DNSKEY".into());
+        }
+        let anchored: Vec<DNSKEY> = keys
+            .iter()
+            .filter(|key| self.anchors.contains(key))
+            .cloned()
+            .collect();
+        if anchored.is_empty() {
+            return Chain::Bogus("no root DNSKEY matches the trust anchor".into());
+        }
+
+        let records = records_of(response.answers(), &root, RecordType::DNSKEY);
+        let sigs = signatures(response.answers(), &root, RecordType::DNSKEY);
+        match verify_rrset(&anchored, &root, RecordType::DNSKEY, &records, &sigs) {
+            Ok(()) => Chain::Secure { zone: root, keys },
+            Err(e) => Chain::Bogus(e),
+        }
+    }
+
+    /// Takes one step down the tree from a secure `parent` chain to `child`.
+    async fn delegation(&self, parent: Chain, child: &Name) -> Step {
+        let Chain::Secure { zone, keys } = parent else {
+            return Step { chain: parent, done: true };
+        };
+
+        let response = match raw_query(self.server, child, RecordType::DS, FLAGS).await {
+            Ok(response) => response,
+            Err(e) => return Step::done(Chain::Indeterminate(e)),
+        };
+
+        // Nothing exists below a missing name; the final answer proves that
+        if response.response_code() == ResponseCode::NXDomain {
+            return Step::done(Chain::Secure { zone, keys });
+        }
+
+        let ds = dss(response.answers(), child);
+        if ds.is_empty() {
+            // A CNAME in the way ends the walk; its target is checked on its own
+            if !response.answers().is_empty() {
+                return Step::done(Chain::Secure { zone, keys });
+            }
+            return self.unsigned_child(zone, keys, child, &response).await;
+        }
+
+        let records = records_of(response.answers(), child, RecordType::DS);
+        let sigs = signatures(response.answers(), child, RecordType::DS);
+        if let Err(e) = verify_rrset(&keys, child, RecordType::DS, &records, &sigs) {
+            return Step::done(Chain::Bogus(e));
+        }
+
+        let response = match raw_query(self.server, child, RecordType::DNSKEY, FLAGS).await {
+            Ok(response) => response,
+            Err(e) => return Step::done(Chain::Indeterminate(e)),
+        };
+
+        let child_keys = dnskeys(response.answers(), child);
+        let entry_keys: Vec<DNSKEY> = child_keys
+            .iter()
+            .filter(|key| ds.iter().any(|ds| ds.covers(child, key).unwrap_or(false)))
+            .cloned()
+            .collect();
+        if entry_keys.is_empty() {
+            return Step::done(Chain::Bogus(format!("no DNSKEY of {} matches its DS in {}", child, zone)));
+        }
+
+        let records = records_of(response.answers(), child, RecordType::DNSKEY);
+        let sigs = signatures(response.answers(), child, RecordType::DNSKEY);
+        match verify_rrset(&entry_keys, child, RecordType::DNSKEY, &records, &sigs) {
+            Ok(()) => Step::next(Chain::Secure { zone: child.clone(), keys: child_keys }),
+            Err(e) => Step::done(Chain::Bogus(e)),
+        }
+    }
+
+    /// No DS for `child`: the parent must sign records proving the absence
+    /// (or opting `child` out of NSEC3), and if `child` turns out to be a
+    /// zone of its own, the chain ends there.
+    async fn unsigned_child(&self, zone: Name, keys: Vec<DNSKEY>, child: &Name, response: &Message) -> Step {
+        let authority = response.name_servers();
+        let denial: Vec<_> = rrsets(authority)
+            .into_iter()
+            .filter(|(_, record_type, _)| matches!(record_type, RecordType::NSEC | RecordType::NSEC3))
+            .collect();
+        if denial.is_empty() {
+            return Step::done(Chain::Bogus(format!("unsigned denial of DS for {} in {}", child, zone)));
+        }
+        for (owner, record_type, records) in denial {
+            let sigs = signatures(authority, &owner, record_type);
+            if let Err(e) = verify_rrset(&keys, &owner, record_type, &records, &sigs) {
+                return Step::done(Chain::Bogus(e));
+            }
+        }
+
+        let proof = Denial::from_records(authority);
+        if !proof.denies(&zone, child, Some(RecordType::DS)) && !proof.opts_out(&zone, child) {
+            return Step::done(Chain::Bogus(format!("no NSEC or NSEC3 record proves that {} has no DS in {}", child, zone)));
+        }
+
+        let response = match raw_query(self.server, child, RecordType::SOA, FLAGS).await {
+            Ok(response) => response,
+            Err(e) => return Step::done(Chain::Indeterminate(e)),
+        };
+
+        let is_zone = response
+            .answers()
+            .iter()
+            .any(|record| record.record_type() == RecordType::SOA && record.name() == child);
+        if is_zone {
+            Step::done(Chain::Insecure(format!("unsigned delegation at {}", child)))
+        } else {
+            Step::next(Chain::Secure { zone, keys })
+        }
+    }
+}
+
+/// A chain after one delegation step, and whether to stop walking
+struct Step {
+    chain: Chain,
+    done: bool,
+}
+
+impl Step {
+    fn next(chain: Chain) -> Self {
+        Self { chain, done: false }
+    }
+
+    fn done(chain: Chain) -> Self {
+        Self { chain, done: true }
+    }
+}
+
+/// The NSEC and NSEC3 records of an authority section, which between them
+/// must prove that a name, or a type at it, does not exist
+struct Denial {
+    nsec: Vec<(Name, NSEC)>,
+    /// With the hash each one is owned by
+    nsec3: Vec<(Vec<u8>, NSEC3)>,
+}
+
+impl Denial {
+    fn from_records(records: &[Record]) -> Self {
+        let mut denial = Self { nsec: Vec::new(), nsec3: Vec::new() };
+        for record in records {
+            match record.data() {
+                Some(RData::DNSSEC(DNSSECRData::NSEC(nsec))) => denial.nsec.push((record.name().clone(), nsec.clone())),
+                Some(RData::DNSSEC(DNSSECRData::NSEC3(nsec3))) => {
+                    let hash = record.name().iter().next().and_then(|label| BASE32_DNSSEC.decode(label).ok());
+                    if let Some(hash) = hash {
+                        denial.nsec3.push((hash, nsec3.clone()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        denial
+    }
+
+    /// Whether `name` provably has no `record_type` records or, for `None`,
+    /// does not exist at all. `zone` is the zone that signed the proof.
+    fn denies(&self, zone: &Name, name: &Name, record_type: Option<RecordType>) -> bool {
+        let lacks = |types: &[RecordType]| match record_type {
+            Some(record_type) => !types.contains(&record_type) && !types.contains(&RecordType::CNAME),
+            None => false,
+        };
+
+        if let Some(types) = self.types_at(name) {
+            return lacks(types);
+        }
+
+        // An empty non-terminal has no NSEC of its own, but names below it
+        let empty_non_terminal = self
+            .nsec
+            .iter()
+            .any(|(owner, nsec)| covers(owner, name, nsec.next_domain_name()) && name.zone_of(nsec.next_domain_name()));
+        if empty_non_terminal {
+            return record_type.is_some();
+        }
+
+        // Otherwise nothing may exist from below the closest encloser down,
+        // and no wildcard there may answer for the name instead
+        let Some(encloser) = self.closest_encloser(zone, name) else {
+            return false;
+        };
+        if !self.covers(&name.trim_to(encloser.num_labels() as usize + 1)) {
+            return false;
+        }
+        let Ok(wildcard) = Name::from_ascii("*").and_then(|star| star.append_domain(&encloser)) else {
+            return false;
+        };
+        match self.types_at(&wildcard) {
+            Some(types) => lacks(types),
+            None => self.covers(&wildcard),
+        }
+    }
+
+    /// Whether `name` falls in an opt-out NSEC3 span, so it may be an
+    /// unsigned delegation without a record of its own
+    fn opts_out(&self, zone: &Name, name: &Name) -> bool {
+        let Some(encloser) = self.closest_encloser(zone, name) else {
+            return false;
+        };
+        let next_closer = name.trim_to(encloser.num_labels() as usize + 1);
+        self.nsec3.iter().any(|(owner, nsec3)| {
+            nsec3.opt_out()
+                && nsec3_hash(nsec3, &next_closer).is_some_and(|hash| covers(owner.as_slice(), &hash, nsec3.next_hashed_owner_name()))
+        })
+    }
+
+    /// The types at `name`, if a record is owned by it
+    fn types_at(&self, name: &Name) -> Option<&[RecordType]> {
+        let nsec = self.nsec.iter().find(|(owner, _)| owner == name).map(|(_, nsec)| nsec.type_bit_maps());
+        nsec.or_else(|| {
+            self.nsec3
+                .iter()
+                .find(|(owner, nsec3)| nsec3_hash(nsec3, name).as_ref() == Some(owner))
+                .map(|(_, nsec3)| nsec3.type_bit_maps())
+        })
+    }
+
+    /// Whether a record spans `name`, so nothing exists there
+    fn covers(&self, name: &Name) -> bool {
+        self.nsec.iter().any(|(owner, nsec)| covers(owner, name, nsec.next_domain_name()))
+            || self.nsec3.iter().any(|(owner, nsec3)| {
+                nsec3_hash(nsec3, name).is_some_and(|hash| covers(owner.as_slice(), &hash, nsec3.next_hashed_owner_name()))
+            })
+    }
+
+    /// The deepest ancestor of `name` that provably exists. With NSEC it is
+    /// the longest name shared with the record spanning `name`; with NSEC3 it
+    /// is the deepest ancestor with a record of its own.
+    fn closest_encloser(&self, zone: &Name, name: &Name) -> Option<Name> {
+        let spanned = self
+            .nsec
+            .iter()
+            .filter(|(owner, nsec)| covers(owner, name, nsec.next_domain_name()))
+            .flat_map(|(owner, nsec)| [common_ancestor(name, owner), common_ancestor(name, nsec.next_domain_name())])
+            .max_by_key(|ancestor| ancestor.num_labels());
+        if let Some(ancestor) = spanned {
+            return zone.zone_of(&ancestor).then_some(ancestor);
+        }
+
+        (zone.num_labels()..name.num_labels())
+            .rev()
+            .map(|labels| name.trim_to(labels as usize))
+            .find(|ancestor| self.nsec3.iter().any(|(owner, nsec3)| nsec3_hash(nsec3, ancestor).as_ref() == Some(owner)))
+    }
+}
+
+/// Whether `value` lies strictly between an NSEC(3) owner and the next name,
+/// where the last record of a zone wraps around to the first
+fn covers<T: Ord + ?Sized>(owner: &T, value: &T, next: &T) -> bool {
+    if owner < next {
+        owner < value && value < next
+    } else {
+        owner < value || value < next
+    }
+}
+
+fn nsec3_hash(nsec3: &NSEC3, name: &Name) -> Option<Vec<u8>> {
+    let hash = nsec3.hash_algorithm().hash(nsec3.salt(), name, nsec3.iterations()).ok()?;
+    Some(hash.as_ref().to_vec())
+}
+
+/// The longest name both `a` and `b` are in
+fn common_ancestor(a: &Name, b: &Name) -> Name {
+    let mut labels = 0;
+    while labels < a.num_labels().min(b.num_labels()) as usize && a.trim_to(labels + 1) == b.trim_to(labels + 1) {
+        labels += 1;
+    }
+    a.trim_to(labels)
+}
+
+fn bogus(detail: String) -> DnssecResult {
+    DnssecResult { status: DnssecStatus::Bogus, detail: Some(detail) }
+}
+
+/// Checks that at least one current RRSIG over the RRset verifies with `keys`.
+fn verify_rrset(
+    keys: &[DNSKEY],
+    owner: &Name,
+    record_type: RecordType,
+    records: &[Record],
+    sigs: &[RRSIG],
+) -> Result<(), String> {
+    if sigs.is_empty() {
+        return Err(format!("missing RRSIG for {} {}", owner, record_type));
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or_default();
+
+    let mut error = format!("no DNSKEY matches the RRSIG on {} {}", owner, record_type);
+    for sig in sigs {
+        if now > sig.sig_expiration() {
+            error = format!("RRSIG on {} {} has expired", owner, record_type);
+            continue;
+        }
+        if now < sig.sig_inception() {
+            error = format!("RRSIG on {} {} is not yet valid", owner, record_type);
+            continue;
+        }
+
+        for key in keys {
+            let tag = key.calculate_key_tag().unwrap_or_default();
+            if tag != sig.key_tag() || key.algorithm() != sig.algorithm() {
+                continue;
+            }
+            match key.verify_rrsig(owner, DNSClass::IN, sig, records) {
+                Ok(()) => return Ok(()),
+                Err(_) => {
+                    error = format!("RRSIG on {} {} does not verify with key {}", owner, record_type, tag);
+                }
+            }
+        }
+    }
+
+    Err(error)
+}
+
+/// Groups a section into RRsets by owner and type, skipping the signatures.
+fn rrsets(records: &[Record]) -> Vec<(Name, RecordType, Vec<Record>)> {
+    let mut sets: Vec<(Name, RecordType, Vec<Record>)> = Vec::new();
+    for record in records {
+        if record.record_type() == RecordType::RRSIG {
+            continue;
+        }
+        match sets
+            .iter_mut()
+            .find(|(name, record_type, _)| name == record.name() && *record_type == record.record_type())
+        {
+            Some((_, _, set)) => set.push(record.clone()),
+            None => sets.push((record.name().clone(), record.record_type(), vec![record.clone()])),
+        }
+    }
+    sets
+}
+
+fn records_of(records: &[Record], owner: &Name, record_type: RecordType) -> Vec<Record> {
+    records
+        .iter()
+        .filter(|record| record.name() == owner && record.record_type() == record_type)
+        .cloned()
+        .collect()
+}
+
+/// The RRSIGs in a section that cover `owner`/`record_type`
+fn signatures(records: &[Record], owner: &Name, record_type: RecordType) -> Vec<RRSIG> {
+    records
+        .iter()
+        .filter(|record| record.name() == owner)
+        .filter_map(|record| match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::RRSIG(sig))) if sig.type_covered() == record_type => Some(sig.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn dnskeys(records: &[Record], owner: &Name) -> Vec<DNSKEY> {
+    records
+        .iter()
+        .filter(|record| record.name() == owner)
+        .filter_map(|record| match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::DNSKEY(key))) => Some(key.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn dss(records: &[Record], owner: &Name) -> Vec<DS> {
+    records
+        .iter()
+        .filter(|record| record.name() == owner)
+        .filter_map(|record| match record.data() {
+            Some(RData::DNSSEC(DNSSECRData::DS(ds))) => Some(ds.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, StubDns};
+    use data_encoding::HEXUPPER;
+    use std::net::Ipv4Addr;
+    use trust_dns_resolver::proto::rr::dnssec::{tbs, KeyFormat, KeyPair, Nsec3HashAlgorithm, Private};
+    use trust_dns_resolver::proto::rr::rdata::SOA;
+
+    type Records = HashMap<(Name, RecordType), (ResponseCode, Vec<Record>, Vec<Record>)>;
+
+    /// A zone with its own Ed25519 key
+    struct Zone {
+        name: Name,
+        key: KeyPair<Private>,
+        dnskey: DNSKEY,
+    }
+
+    impl Zone {
+        fn new(name: &str) -> Self {
+            let pkcs8 = KeyPair::generate_pkcs8(Algorithm::ED25519).unwrap();
+            let key = KeyFormat::Pkcs8.decode_key(&pkcs8, None, Algorithm::ED25519).unwrap();
+            let dnskey = key.to_dnskey(Algorithm::ED25519).unwrap();
+            Self { name: testing::name(name), key, dnskey }
+        }
+
+        fn tag(&self) -> u16 {
+            self.dnskey.calculate_key_tag().unwrap()
+        }
+
+        fn ds(&self) -> DS {
+            self.key.to_ds(&self.name, Algorithm::ED25519, DigestType::SHA256).unwrap()
+        }
+
+        fn dnskey_record(&self) -> Record {
+            Record::from_rdata(self.name.clone(), 60, RData::DNSSEC(DNSSECRData::DNSKEY(self.dnskey.clone())))
+        }
+
+        fn ds_record(&self) -> Record {
+            Record::from_rdata(self.name.clone(), 60, RData::DNSSEC(DNSSECRData::DS(self.ds())))
+        }
+
+        /// An RRSIG over `records`, valid from `inception` to `expiration`
+        fn rrsig_between(&self, records: &[Record], inception: u32, expiration: u32) -> RRSIG {
+            let owner = records[0].name();
+            let record_type = records[0].record_type();
+            let labels = owner.num_labels();
+            let tbs = tbs::rrset_tbs(
+                owner,
+                DNSClass::IN,
+                labels,
+                record_type,
+                Algorithm::ED25519,
+                60,
+                expiration,
+                inception,
+                self.tag(),
+                &self.name,
+                records,
+            )
+            .unwrap();
+            let sig = self.key.sign(Algorithm::ED25519, &tbs).unwrap();
+            RRSIG::new(record_type, Algorithm::ED25519, labels, 60, expiration, inception, self.tag(), self.name.clone(), sig)
+        }
+
+        fn rrsig(&self, records: &[Record]) -> RRSIG {
+            self.rrsig_between(records, now() - 3600, now() + 3600)
+        }
+
+        /// `records` followed by their RRSIG
+        fn sign(&self, mut records: Vec<Record>) -> Vec<Record> {
+            let sig = self.rrsig(&records);
+            records.push(Record::from_rdata(records[0].name().clone(), 60, RData::DNSSEC(DNSSECRData::RRSIG(sig))));
+            records
+        }
+    }
+
+    fn nsec_record(owner: &str, next: &str, types: Vec<RecordType>) -> Record {
+        let nsec = NSEC::new_cover_self(testing::name(next), types);
+        Record::from_rdata(testing::name(owner), 60, RData::DNSSEC(DNSSECRData::NSEC(nsec)))
+    }
+
+    fn hash(name: &str) -> Vec<u8> {
+        Nsec3HashAlgorithm::SHA1.hash(&[], &testing::name(name), 0).unwrap().as_ref().to_vec()
+    }
+
+    /// An unsalted NSEC3 record for `name` in `zone`, spanning to the next
+    /// hash up
+    fn nsec3_record(zone: &str, name: &str, opt_out: bool, types: Vec<RecordType>) -> Record {
+        nsec3_span(zone, hash(name), next_hash(hash(name)), opt_out, types)
+    }
+
+    fn nsec3_span(zone: &str, owner: Vec<u8>, next: Vec<u8>, opt_out: bool, types: Vec<RecordType>) -> Record {
+        let owner = testing::name(&format!("{}.{}", BASE32_DNSSEC.encode(&owner), zone.trim_start_matches('.')));
+        let nsec3 = NSEC3::new(Nsec3HashAlgorithm::SHA1, opt_out, 0, Vec::new(), next, types);
+        Record::from_rdata(owner, 60, RData::DNSSEC(DNSSECRData::NSEC3(nsec3)))
+    }
+
+    fn next_hash(mut hash: Vec<u8>) -> Vec<u8> {
+        *hash.last_mut().unwrap() += 1;
+        hash
+    }
+
+    fn now() -> u32 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32
+    }
+
+    /// A signed root with a secure child (example.), children delegated
+    /// without a DS by NSEC (unsigned.) and NSEC3 (hashed.), one whose DS
+    /// was stripped behind an NSEC for other names (stripped.) and one whose
+    /// DS matches none of its keys (broken.). Returns the root, to anchor
+    /// on, and example.
+    fn signed_tree() -> (Zone, Zone, Records) {
+        let root = Zone::new(".");
+        let example = Zone::new("example.");
+        let broken = Zone::new("broken.");
+        let stranger = Zone::new("broken.");
+
+        let mut records = Records::new();
+        let mut add = |name: &str, record_type, answers, authority| {
+            records.insert((testing::name(name), record_type), (ResponseCode::NoError, answers, authority));
+        };
+
+        add(".", RecordType::DNSKEY, root.sign(vec![root.dnskey_record()]), vec![]);
+
+        add("example.", RecordType::DS, root.sign(vec![example.ds_record()]), vec![]);
+        add("example.", RecordType::DNSKEY, example.sign(vec![example.dnskey_record()]), vec![]);
+        add(
+            "www.example.",
+            RecordType::A,
+            example.sign(vec![testing::a("www.example.", Ipv4Addr::new(192, 0, 2, 1))]),
+            vec![],
+        );
+        // Signed over one address, served with another
+        let mut tampered = example.sign(vec![testing::a("bad.example.", Ipv4Addr::new(192, 0, 2, 2))]);
+        tampered[0] = testing::a("bad.example.", Ipv4Addr::new(192, 0, 2, 66));
+        add("bad.example.", RecordType::A, tampered, vec![]);
+
+        let nsec = NSEC::new(testing::name("www.unsigned."), vec![RecordType::NS, RecordType::NSEC, RecordType::RRSIG]);
+        let nsec = Record::from_rdata(testing::name("unsigned."), 60, RData::DNSSEC(DNSSECRData::NSEC(nsec)));
+        add("unsigned.", RecordType::DS, vec![], root.sign(vec![nsec]));
+        let soa = SOA::new(testing::name("ns.unsigned."), testing::name("hostmaster.unsigned."), 1, 3600, 600, 86400, 60);
+        add("unsigned.", RecordType::SOA, vec![Record::from_rdata(testing::name("unsigned."), 60, RData::SOA(soa))], vec![]);
+        add("www.unsigned.", RecordType::A, vec![testing::a("www.unsigned.", Ipv4Addr::new(192, 0, 2, 3))], vec![]);
+
+        let nsec3 = nsec3_record(".", "hashed.", false, vec![RecordType::NS]);
+        add("hashed.", RecordType::DS, vec![], root.sign(vec![nsec3]));
+        let soa = SOA::new(testing::name("ns.hashed."), testing::name("hostmaster.hashed."), 1, 3600, 600, 86400, 60);
+        add("hashed.", RecordType::SOA, vec![Record::from_rdata(testing::name("hashed."), 60, RData::SOA(soa))], vec![]);
+        add("www.hashed.", RecordType::A, vec![testing::a("www.hashed.", Ipv4Addr::new(192, 0, 2, 5))], vec![]);
+
+        add("stripped.", RecordType::DS, vec![], root.sign(vec![nsec_record("alpha.", "beta.", vec![RecordType::NS])]));
+        let soa = SOA::new(testing::name("ns.stripped."), testing::name("hostmaster.stripped."), 1, 3600, 600, 86400, 60);
+        add("stripped.", RecordType::SOA, vec![Record::from_rdata(testing::name("stripped."), 60, RData::SOA(soa))], vec![]);
+        add("www.stripped.", RecordType::A, vec![testing::a("www.stripped.", Ipv4Addr::new(192, 0, 2, 6))], vec![]);
+
+        add("broken.", RecordType::DS, root.sign(vec![stranger.ds_record()]), vec![]);
+        add("broken.", RecordType::DNSKEY, broken.sign(vec![broken.dnskey_record()]), vec![]);
+        add(
+            "www.broken.",
+            RecordType::A,
+            broken.sign(vec![testing::a("www.broken.", Ipv4Addr::new(192, 0, 2, 4))]),
+            vec![],
+        );
+
+        // Denials inside example.: a proper NXDOMAIN and NODATA, and an
+        // NXDOMAIN backed by a record for names after the missing one
+        let nsec = nsec_record("example.", "www.example.", vec![RecordType::SOA, RecordType::NS, RecordType::DNSKEY]);
+        records.insert(
+            (testing::name("gone.example."), RecordType::A),
+            (ResponseCode::NXDomain, vec![], example.sign(vec![nsec])),
+        );
+        let nsec = nsec_record("www.example.", "zzz.example.", vec![RecordType::A]);
+        records.insert(
+            (testing::name("www.example."), RecordType::AAAA),
+            (ResponseCode::NoError, vec![], example.sign(vec![nsec.clone()])),
+        );
+        records.insert(
+            (testing::name("fake.example."), RecordType::A),
+            (ResponseCode::NXDomain, vec![], example.sign(vec![nsec])),
+        );
+
+        (root, example, records)
+    }
+
+    /// Serves the signed tree, and a validator anchored on its root by DS
+    async fn validator() -> (StubDns, Validator, Zone) {
+        let (root, example, records) = signed_tree();
+        let stub = StubDns::start(move |request: &Message| {
+            let query = &request.queries()[0];
+            match records.get(&(query.name().clone(), query.query_type())) {
+                Some((code, answers, authority)) => {
+                    let mut response = testing::reply(request, *code);
+                    response.add_answers(answers.clone()).add_name_servers(authority.clone());
+                    response
+                }
+                None => testing::reply(request, ResponseCode::NXDomain),
+            }
+        })
+        .await;
+
+        let ds = root.ds();
+        let anchor = format!(
+            ". 172800 IN DS {} {} {} {}",
+            ds.key_tag(),
+            u8::from(ds.algorithm()),
+            u8::from(ds.digest_type()),
+            HEXUPPER.encode(ds.digest())
+        );
+        let server = RawServer { addr: stub.addr, tcp: false };
+        (stub, Validator::new(server, TrustAnchors::parse(&anchor).unwrap()), example)
+    }
+
+    fn status(result: &DnssecResult) -> (DnssecStatus, Option<&str>) {
+        (result.status, result.detail.as_deref())
+    }
+
+    #[tokio::test]
+    async fn validates_answers_against_the_chain_of_trust() {
+        let (_stub, mut validator, example) = validator().await;
+
+        let result = validator.validate(&testing::name("www.example."), RecordType::A).await;
+        assert_eq!(status(&result), (DnssecStatus::Secure, None));
+
+        let result = validator.validate(&testing::name("www.unsigned."), RecordType::A).await;
+        assert_eq!(status(&result), (DnssecStatus::Insecure, Some("unsigned delegation at unsigned.")));
+
+        let result = validator.validate(&testing::name("www.broken."), RecordType::A).await;
+        assert_eq!(status(&result), (DnssecStatus::Bogus, Some("no DNSKEY of broken. matches its DS in .")));
+
+        let result = validator.validate(&testing::name("bad.example."), RecordType::A).await;
+        let detail = format!("RRSIG on bad.example. A does not verify with key {}", example.tag());
+        assert_eq!(status(&result), (DnssecStatus::Bogus, Some(detail.as_str())));
+    }
+
+    #[tokio::test]
+    async fn checks_that_denials_cover_the_name() {
+        let (_stub, mut validator, _) = validator().await;
+
+        let result = validator.validate(&testing::name("www.hashed."), RecordType::A).await;
+        assert_eq!(status(&result), (DnssecStatus::Insecure, Some("unsigned delegation at hashed.")));
+
+        let result = validator.validate(&testing::name("www.stripped."), RecordType::A).await;
+        let detail = "no NSEC or NSEC3 record proves that stripped. has no DS in .";
+        assert_eq!(status(&result), (DnssecStatus::Bogus, Some(detail)));
+
+        let result = validator.validate(&testing::name("gone.example."), RecordType::A).await;
+        assert_eq!(status(&result), (DnssecStatus::Secure, None));
+
+        let result = validator.validate(&testing::name("www.example."), RecordType::AAAA).await;
+        assert_eq!(status(&result), (DnssecStatus::Secure, None));
+
+        let result = validator.validate(&testing::name("fake.example."), RecordType::A).await;
+        let detail = "no NSEC or NSEC3 record proves that fake.example. does not exist";
+        assert_eq!(status(&result), (DnssecStatus::Bogus, Some(detail)));
+    }
+
+    #[tokio::test]
+    async fn chain_for_follows_delegations_from_the_root() {
+        let (_stub, mut validator, example) = validator().await;
+
+        match validator.chain_for(&testing::name("example.")).await {
+            Chain::Secure { zone, keys } => {
+                assert_eq!(zone, testing::name("example."));
+                assert_eq!(keys, vec![example.dnskey.clone()]);
+            }
+            chain => panic!("expected a secure chain, got {:?}", chain),
+        }
+        assert!(validator.chains.contains_key(&Name::root()));
+        assert!(validator.chains.contains_key(&testing::name("example.")));
+
+        // The chain ends at the unsigned delegation, however deep the name
+        let chain = validator.chain_for(&testing::name("a.b.unsigned.")).await;
+        assert!(matches!(chain, Chain::Insecure(detail) if detail == "unsigned delegation at unsigned."));
+        assert!(!validator.chains.contains_key(&testing::name("b.unsigned.")));
+
+        let chain = validator.chain_for(&testing::name("broken.")).await;
+        assert!(matches!(chain, Chain::Bogus(_)));
+    }
+
+    #[tokio::test]
+    async fn rejects_a_root_key_outside_the_trust_anchor() {
+        let (stub, _, _) = validator().await;
+        let mut validator = Validator::new(RawServer { addr: stub.addr, tcp: false }, TrustAnchors::default());
+
+        let result = validator.validate(&testing::name("www.example."), RecordType::A).await;
+        assert_eq!(status(&result), (DnssecStatus::Bogus, Some("no root DNSKEY matches the trust anchor")));
+    }
+
+    #[test]
+    fn verify_rrset_checks_keys_and_validity() {
+        let zone = Zone::new("example.");
+        let owner = testing::name("www.example.");
+        let records = vec![testing::a("www.example.", Ipv4Addr::new(192, 0, 2, 1))];
+        let keys = vec![zone.dnskey.clone()];
+        let verify = |keys: &[DNSKEY], records: &[Record], sigs: &[RRSIG]| verify_rrset(keys, &owner, RecordType::A, records, sigs);
+
+        assert_eq!(verify(&keys, &records, &[zone.rrsig(&records)]), Ok(()));
+
+        // One good signature among stale ones is enough
+        let expired = zone.rrsig_between(&records, now() - 7200, now() - 3600);
+        assert_eq!(verify(&keys, &records, &[expired.clone(), zone.rrsig(&records)]), Ok(()));
+
+        assert_eq!(verify(&keys, &records, &[]), Err("missing RRSIG for www.example. A".into()));
+        assert_eq!(verify(&keys, &records, &[expired]), Err("RRSIG on www.example. A has expired".into()));
+
+        let early = zone.rrsig_between(&records, now() + 3600, now() + 7200);
+        assert_eq!(verify(&keys, &records, &[early]), Err("RRSIG on www.example. A is not yet valid".into()));
+
+        let other = Zone::new("example.");
+        assert_eq!(
+            verify(std::slice::from_ref(&other.dnskey), &records, &[zone.rrsig(&records)]),
+            Err("no DNSKEY matches the RRSIG on www.example. A".into())
+        );
+
+        let altered = vec![testing::a("www.example.", Ipv4Addr::new(192, 0, 2, 66))];
+        assert_eq!(
+            verify(&keys, &altered, &[zone.rrsig(&records)]),
+            Err(format!("RRSIG on www.example. A does not verify with key {}", zone.tag()))
+        );
+    }
+
+    #[test]
+    fn nsec_denials_need_the_name_and_its_wildcard_covered() {
+        let zone = testing::name("example.");
+        let name = testing::name("b.example.");
+        let denial = |records: Vec<Record>| Denial::from_records(&records);
+
+        // Missing *.example. might have answered for b.example.
+        let spanning = nsec_record("a.example.", "c.example.", vec![RecordType::A]);
+        assert!(!denial(vec![spanning.clone()]).denies(&zone, &name, None));
+
+        let apex = nsec_record("example.", "a.example.", vec![RecordType::SOA]);
+        assert!(denial(vec![spanning.clone(), apex]).denies(&zone, &name, None));
+
+        let wildcard = nsec_record("*.example.", "a.example.", vec![RecordType::TXT]);
+        let proof = denial(vec![spanning, wildcard]);
+        assert!(proof.denies(&zone, &name, Some(RecordType::A)));
+        assert!(!proof.denies(&zone, &name, Some(RecordType::TXT)));
+        assert!(!proof.denies(&zone, &name, None));
+
+        // b.example. exists when only names below it do
+        let proof = denial(vec![nsec_record("a.example.", "x.b.example.", vec![RecordType::A])]);
+        assert!(proof.denies(&zone, &name, Some(RecordType::A)));
+        assert!(!proof.denies(&zone, &name, None));
+
+        let proof = denial(vec![nsec_record("b.example.", "c.example.", vec![RecordType::NS, RecordType::DS])]);
+        assert!(proof.denies(&zone, &name, Some(RecordType::MX)));
+        assert!(!proof.denies(&zone, &name, Some(RecordType::DS)));
+    }
+
+    #[test]
+    fn nsec3_denials_need_a_matching_hash_or_an_opt_out_span() {
+        let zone = testing::name("example.");
+        let child = testing::name("child.example.");
+        let apex = nsec3_record("example.", "example.", false, vec![RecordType::SOA, RecordType::NS]);
+        let everything = |opt_out| nsec3_span("example.", vec![0; 20], vec![0xff; 20], opt_out, vec![]);
+
+        let proof = Denial::from_records(&[nsec3_record("example.", "child.example.", false, vec![RecordType::NS])]);
+        assert!(proof.denies(&zone, &child, Some(RecordType::DS)));
+        assert!(!proof.denies(&zone, &testing::name("other.example."), Some(RecordType::DS)));
+
+        let proof = Denial::from_records(&[apex.clone(), everything(true)]);
+        assert!(proof.opts_out(&zone, &child));
+
+        let proof = Denial::from_records(&[apex, everything(false)]);
+        assert!(!proof.opts_out(&zone, &child));
+        // Both child.example. and *.example. fall in the span
+        assert!(proof.denies(&zone, &child, None));
+
+        let proof = Denial::from_records(&[everything(true)]);
+        assert!(!proof.opts_out(&zone, &child));
+    }
+
+    #[test]
+    fn parses_trust_anchor_files() {
+        let zone = Zone::new(".");
+        let dnskey = format!(
+            ". 172800 IN DNSKEY 257 3 15 {} ; test root",
+            data_encoding::BASE64.encode(zone.dnskey.public_key())
+        );
+        let anchors = TrustAnchors::parse(&format!("; root keys\n\n{}\n", dnskey)).unwrap();
+        assert!(anchors.contains(&zone.dnskey));
+        assert!(!anchors.contains(&Zone::new(".").dnskey));
+
+        let anchors = TrustAnchors::parse("DS 20326 8 2 e06d44b80b8f1d39a95c0b0d7c65d08458e880409bbc683457104237c7f8ec8d").unwrap();
+        assert_eq!(anchors.ds[0].key_tag(), 20326);
+        assert_eq!(anchors.ds[0].digest_type(), DigestType::SHA256);
+
+        assert!(TrustAnchors::parse("example. IN DS 1 8 2 AB").err().unwrap().contains("not for the root zone"));
+        assert!(TrustAnchors::parse(". IN DS 1 8 2 XYZ").err().unwrap().contains("invalid DS record"));
+        assert!(TrustAnchors::parse(". IN DNSKEY 257 3 8").err().unwrap().contains("invalid DNSKEY record"));
+        assert!(TrustAnchors::parse(". IN A 192.0.2.1").err().unwrap().contains("expected a DS or DNSKEY record"));
+        assert_eq!(TrustAnchors::parse("; nothing\n").err().unwrap(), "no DS or DNSKEY records found");
+    }
+
+    #[test]
+    fn default_anchors_include_ksk_2024() {
+        let anchors = TrustAnchors::default();
+        assert_eq!(anchors.keys.len(), 2);
+        assert!(anchors.ds.iter().any(|ds| ds.key_tag() == 38696 && ds.algorithm() == Algorithm::RSASHA256));
+    }
+}
@@ -8,23 +8,40 @@
 // Full license text available in LICENSE and EULA.md.
 
 use reqwest::Client;
+use serde::Serialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::time::Instant;
 
+use crate::dns::IpFamily;
+
+#[derive(Serialize, Debug, Clone)]
 pub struct HttpResult {
     pub status: u16,
     pub time_ms: u128,
     pub final_url: String,
+    /// The address the final request connected to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_addr: Option<String>,
 }
 
-pub async fn check_http(target: &str) -> Result<HttpResult, String> {
+/// Sends a HEAD request, following up to 5 redirects. With a `family`, the
+/// connection is bound to that family's unspecified address so it cannot
+/// fall back to the other one.
+pub async fn check_http(target: &str, family: Option<IpFamily>) -> Result<HttpResult, String> {
     let url = if target.starts_with("http://") || target.starts_with("https://") {
         target.to_string()
     } else {
         format!("https://{}", target)
     };
 
+    let local_address = family.map(|family| match family {
+        IpFamily::Ipv4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpFamily::Ipv6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    });
+
     let client = Client::builder()
         .redirect(reqwest::redirect::Policy::limited(5))
+        .local_address(local_address)
         .build()
         .map_err(|e| format!("HTTP client init failed: {}", e))?;
 
@@ -39,10 +56,12 @@ pub async fn check_http(target: &str) -> Result<HttpResult, String> {
     let elapsed = start.elapsed().as_millis();
     let status = resp.status().as_u16();
     let final_url = resp.url().to_string();
+    let remote_addr = resp.remote_addr().map(|addr| addr.ip().to_string());
 
     Ok(HttpResult {
         status,
         time_ms: elapsed,
         final_url,
+        remote_addr,
     })
 }
\ No newline at end of file
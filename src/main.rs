@@ -7,59 +7,30 @@
 //
 // Full license text available in LICENSE and EULA.md.
 
+mod cli;
 mod config;
 mod first_run;
 mod dns;
+mod dnssec;
+mod delegation;
 mod ping;
 mod http;
 mod trace;
+mod mtr;
+mod report;
+mod output;
+#[cfg(test)]
+mod testing;
 
 use clap::Parser;
+use cli::{Cli, Command};
 use config::Config;
-
-/// Simple aligned key/value printer
-fn kv(label: &str, value: impl std::fmt::Display) {
-    println!("  {:<12} {}", label, value);
-}
-
-/// CLI argument structure
-#[derive(Parser, Debug)]
-#[command(
-    name = "StaxPing",
-    version = "0.1.0",
-    about = "A clean, unified network diagnostic tool by StaxDash.",
-    long_about = "StaxPing performs DNS lookup, ICMP ping, HTTP checks, and optional traceroute.\n\
-It provides a clean, unified interface for quick network diagnostics.",
-    override_usage = "staxping [TARGET] [OPTIONS]",
-    help_template = "
-{name} v{version}
-{about}
-
-USAGE:
-  {usage}
-
-ARGS:
-{positionals}
-
-OPTIONS:
-{options}
-"
-)]
-struct Cli {
-    /// The target domain or IP to test
-    target: Option<String>,
-
-    /// Enable traceroute
-    #[arg(long)]
-    trace: bool,
-
-    /// Advanced mode (future)
-    #[arg(short = 'A', long)]
-    advanced: bool,
-}
+use dns::DnsOptions;
+use output::OutputFormat;
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     // Load config
     let config = Config::load();
 
@@ -67,134 +38,106 @@ async fn main() {
         Some(cfg) => {
             if cfg.needs_first_run() {
                 first_run::run_first_run();
-                return;
+                return ExitCode::SUCCESS;
             }
         }
         None => {
             first_run::run_first_run();
-            return;
+            return ExitCode::SUCCESS;
         }
     }
 
     // Parse CLI arguments
     let cli = Cli::parse();
 
+    // -4 / -6 constrain name resolution everywhere
+    let family = cli.family();
+    let scoped = |options: DnsOptions| DnsOptions { family, ..options };
+
+    // Run a single stage if a subcommand was given
+    if let Some(command) = cli.command {
+        let format = cli.format;
+        let (report, max_loss) = match command {
+            Command::Dns(args) if args.compare => {
+                (report::run_dns_comparison(&args.target, &scoped(args.dns.options())).await, 100.0)
+            }
+            Command::Dns(args) if args.trace => {
+                (report::run_dns_delegation(&args.target, &args.delegation_options()).await, 100.0)
+            }
+            Command::Dns(args) => (report::run_dns(&args.target, &scoped(args.dns.options())).await, 100.0),
+            Command::Ping(args) if args.continuous => {
+                let mut options = args.ping.options();
+                options.continuous = true;
+
+                output::print_live_start(&args.target, format);
+                let dns = scoped(args.resolver.options());
+                let report = report::run_ping_live(&args.target, &dns, &options, &mut |reply| {
+                    output::print_live_reply(reply, format)
+                })
+                .await;
+                output::print_live_summary(&report, format);
+
+                return exit_code(&report, args.ping.max_loss);
+            }
+            Command::Ping(args) if args.ping.all_addresses => (
+                report::run_ping_all(&args.target, &scoped(args.resolver.options()), &args.ping.options()).await,
+                args.ping.max_loss,
+            ),
+            Command::Ping(args) => (
+                report::run_ping(&args.target, &scoped(args.resolver.options()), &args.ping.options()).await,
+                args.ping.max_loss,
+            ),
+            Command::Http(args) => (report::run_http(&args.target, family).await, 100.0),
+            Command::Trace(args) => {
+                (report::run_trace(&args.target, &scoped(args.resolver.options()), &args.trace.options()).await, 100.0)
+            }
+            Command::Mtr(args) => {
+                let dns = scoped(args.resolver.options());
+                let report = report::run_mtr(&args.target, &dns, &args.options(), &mut |result| {
+                    output::print_live_mtr(&args.target, result, format)
+                })
+                .await;
+                output::clear_live_mtr(format);
+                (report, 100.0)
+            }
+        };
+        output::print_report(&report, format);
+        return exit_code(&report, max_loss);
+    }
+
     // If no target provided -> show friendly hint
     if cli.target.is_none() {
         println!("StaxPing needs a target to run diagnostics.\n");
         println!("Try:");
         println!("  staxping example.com");
         println!("  staxping example.com --trace");
+        println!("  staxping ping example.com");
         println!("  staxping --help");
-        return;
+        return ExitCode::SUCCESS;
     }
 
     // Extract target
     let target = cli.target.unwrap();
 
-    // Top-level banner
-    println!("========================================");
-    println!("  StaxPing v0.1.0 — Network Diagnostics");
-    println!("  Target: {}", target);
-    println!("========================================\n");
-
-    // DNS Section
-    println!("=== DNS ===============================");
-
-    let dns_result = match dns::resolve_domain(&target).await {
-        Ok(result) => {
-            if !result.ipv4.is_empty() {
-                kv("IPv4:", format!("{:?}", result.ipv4));
-            }
-            if !result.ipv6.is_empty() {
-                kv("IPv6:", format!("{:?}", result.ipv6));
-            }
-            kv("Lookup:", format!("{} ms", result.lookup_ms));
-            result
-        }
-        Err(e) => {
-            kv("DNS error:", e);
-            return;
-        }
-    };
-
-    // Use the first IPv4 address for ping
-    let ping_ip = if !dns_result.ipv4.is_empty() {
-        dns_result.ipv4[0].clone()
-    } else if !dns_result.ipv6.is_empty() {
-        dns_result.ipv6[0].clone()
-    } else {
-        println!("No valid IPs found for ping.");
-        return;
+    let options = report::RunOptions {
+        trace: cli.trace.then(|| cli.trace_flags.options()),
+        all_addresses: cli.ping.all_addresses,
+        dual_stack: cli.dual_stack,
+        dns: scoped(cli.dns.options()),
+        ping: cli.ping.options(),
     };
 
-    // Ping Section
-    println!("\n=== Ping ==============================");
-
-    match ping::run_ping(&ping_ip).await {
-        Ok(result) => {
-            kv("Sent:", result.sent);
-            kv("Received:", result.received);
-            kv("Loss:", format!("{:.1}%", result.loss));
-            kv("Min:", format!("{:.2} ms", result.min_ms));
-            kv("Avg:", format!("{:.2} ms", result.avg_ms));
-            kv("Max:", format!("{:.2} ms", result.max_ms));
-        }
-        Err(e) => {
-            kv("Ping error:", e);
-        }
-    }
-
-    // HTTP Section
-    println!("\n=== HTTP ==============================");
+    let report = report::run_diagnostics(&target, &options).await;
+    output::print_report(&report, cli.format);
 
-    match http::check_http(&target).await {
-        Ok(result) => {
-            kv("Status:", result.status);
-            kv("Time:", format!("{} ms", result.time_ms));
-            kv("Final URL:", result.final_url);
-        }
-        Err(e) => {
-            kv("HTTP error:", e);
-        }
+    if cli.advanced && cli.format == OutputFormat::Text {
+        println!("\n(advanced mode enabled)");
     }
 
-    // Traceroute Section
-    if cli.trace {
-        println!("\n=== Traceroute ========================");
-
-        // Use the first IPv4 for traceroute
-        let trace_ip = if !dns_result.ipv4.is_empty() {
-            dns_result.ipv4[0].clone()
-        } else if !dns_result.ipv6.is_empty() {
-            dns_result.ipv6[0].clone()
-        } else {
-            println!("No valid IPs found for traceroute.");
-            return;
-        };
-
-        match trace::run_trace(&trace_ip).await {
-            Ok(result) => {
-                for hop in result.hops {
-                    let times: Vec<String> = hop.times_ms.iter()
-                        .map(|t| format!("{:.2} ms", t))
-                        .collect();
-
-                    println!(
-                        "  {:>2}  {:<15}  {}",
-                        hop.hop,
-                        hop.ip,
-                        times.join("  ")
-                    );
-                }
-            }
-            Err(e) => {
-                kv("Trace error:", e);
-            }
-        }
-    }
+    exit_code(&report, cli.ping.max_loss)
+}
 
-    if cli.advanced {
-        println!("\n(advanced mode enabled)");
-    }
-}
\ No newline at end of file
+/// Converts the report outcome into the process exit code
+fn exit_code(report: &report::Report, max_loss: f32) -> ExitCode {
+    ExitCode::from(report.exit_status(max_loss) as u8)
+}
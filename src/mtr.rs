@@ -0,0 +1,303 @@
+// StaxPing — Unified Network Diagnostics
+// Copyright (c) 2026 StaxDash
+//
+// This source code is provided under the StaxPing Source‑Available License & EULA.
+// You may view, modify, and redistribute this code for personal or internal use.
+// Commercial use of any kind requires explicit written permission from StaxDash.
+//
+// Full license text available in LICENSE and EULA.md.
+
+use serde::Serialize;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::dns::{DnsOptions, PtrCache};
+use crate::trace::{self, Prober, TraceOptions, TraceResult, MAX_HOPS};
+
+/// Settings for an MTR run
+#[derive(Debug, Clone, Copy)]
+pub struct MtrOptions {
+    /// Rounds of probes to send; `None` runs until Ctrl-C
+    pub cycles: Option<u32>,
+    /// Pause between rounds
+    pub interval: Duration,
+    /// How each round probes; `probes` go to every hop per round
+    pub trace: TraceOptions,
+}
+
+/// Per-hop statistics gathered over every round so far
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct MtrResult {
+    pub cycles: u32,
+    pub hops: Vec<HopStats>,
+    /// Why the latest failed round could not be sent; its probes count as lost
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct HopStats {
+    pub hop: u32,
+    pub host: String,
+    /// First address that answered at this hop, or "*" so far
+    pub ip: String,
+    pub sent: u32,
+    pub received: u32,
+    pub loss: f32,
+    pub last_ms: Option<f64>,
+    pub avg_ms: f64,
+    pub best_ms: f64,
+    pub worst_ms: f64,
+    pub stddev_ms: f64,
+    #[serde(skip)]
+    sum_sq: f64,
+}
+
+impl HopStats {
+    fn record(&mut self, time_ms: Option<f64>) {
+        self.sent += 1;
+
+        if let Some(time) = time_ms {
+            let previous = self.received as f64;
+            self.received += 1;
+            self.last_ms = Some(time);
+            self.best_ms = if previous == 0.0 { time } else { self.best_ms.min(time) };
+            self.worst_ms = self.worst_ms.max(time);
+            self.avg_ms = (self.avg_ms * previous + time) / self.received as f64;
+            self.sum_sq += time * time;
+
+            let variance = self.sum_sq / self.received as f64 - self.avg_ms * self.avg_ms;
+            self.stddev_ms = variance.max(0.0).sqrt();
+        }
+
+        self.loss = (self.sent - self.received) as f32 / self.sent as f32 * 100.0;
+    }
+}
+
+impl MtrResult {
+    /// Folds one round of probes into the statistics.
+    fn record(&mut self, round: &TraceResult) {
+        self.cycles += 1;
+
+        for trace_hop in &round.hops {
+            let index = trace_hop.hop as usize - 1;
+            while self.hops.len() <= index {
+                let hop = self.hops.len() as u32 + 1;
+                self.hops.push(HopStats {
+                    hop,
+                    host: "*".into(),
+                    ip: "*".into(),
+                    ..HopStats::default()
+                });
+            }
+
+            let stats = &mut self.hops[index];
+            if stats.ip == "*" && trace_hop.ip != "*" {
+                stats.ip = trace_hop.ip.clone();
+                stats.host = trace_hop.host.clone();
+            }
+            for probe in &trace_hop.probes {
+                stats.record(probe.time_ms);
+            }
+        }
+    }
+
+    /// Counts a round that could not be sent as lost at every known hop.
+    fn record_failure(&mut self, error: String) {
+        self.cycles += 1;
+        for stats in &mut self.hops {
+            stats.record(None);
+        }
+        self.last_error = Some(error);
+    }
+}
+
+/// Where each round's probes come from
+enum Source {
+    Prober(Prober),
+    /// Path of the system traceroute, run once per round
+    System(&'static str),
+}
+
+/// Probes every hop to `ip` once per round, handing the updated statistics
+/// to `on_cycle` after each round. Stops after the configured number of
+/// rounds, or on Ctrl-C.
+pub async fn run_mtr(
+    ip: &str,
+    options: &MtrOptions,
+    dns: &DnsOptions,
+    on_cycle: &mut (dyn FnMut(&MtrResult) + Send),
+) -> Result<MtrResult, String> {
+    let dest: IpAddr = ip
+        .parse()
+        .map_err(|_| format!("Invalid IP address for traceroute: {}", ip))?;
+
+    if options.trace.multipath.is_some() {
+        return Err("mtr follows one flow per hop; list every next hop with trace --multipath".into());
+    }
+
+    // Without a working resolver the hops simply keep their addresses
+    let mut names = if options.trace.numeric {
+        None
+    } else {
+        PtrCache::new(dns).await.ok()
+    };
+
+    // One prober for the whole run, so every round goes out on the same
+    // flows and --paris statistics describe a single path. Without the
+    // privileges for its raw sockets, each round runs the system traceroute
+    // instead, as trace does.
+    let mut source = match Prober::new(dest, &options.trace) {
+        Ok(prober) => Source::Prober(prober),
+        Err(e) => match trace::system_traceroute() {
+            Some(path) if !options.trace.paris => Source::System(path),
+            Some(_) => return Err(format!("{}; --paris needs the built-in tracer", e)),
+            None => return Err(format!("{}, and no system traceroute is installed", e)),
+        },
+    };
+    let mut result = MtrResult::default();
+    let mut max_hops = MAX_HOPS;
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    while options.cycles.is_none_or(|cycles| result.cycles < cycles) {
+        if result.cycles > 0 {
+            tokio::select! {
+                _ = tokio::time::sleep(options.interval) => {}
+                _ = &mut ctrl_c => break,
+            }
+        }
+
+        let round = match source {
+            Source::Prober(mut prober) => {
+                let task = tokio::task::spawn_blocking(move || {
+                    let round = prober.trace(max_hops);
+                    (prober, round)
+                });
+                let (returned, round) = tokio::select! {
+                    joined = task => match joined {
+                        Ok(joined) => joined,
+                        // The prober went down with the task, but the rounds so far still count
+                        Err(e) => {
+                            result.record_failure(format!("Traceroute task failed: {}", e));
+                            break;
+                        }
+                    },
+                    _ = &mut ctrl_c => break,
+                };
+                source = Source::Prober(returned);
+                round
+            }
+            Source::System(path) => tokio::select! {
+                round = trace::run_system_traceroute(path, ip, &options.trace, max_hops) => round,
+                _ = &mut ctrl_c => break,
+            },
+        };
+
+        let mut round = match round {
+            Ok(round) => round,
+            // A send error, e.g. while the network is down, or a failed
+            // system traceroute costs one round
+            Err(e) => {
+                result.record_failure(e);
+                on_cycle(&result);
+                continue;
+            }
+        };
+
+        // Once the destination answers, later rounds stop where it did
+        // instead of flooding it with probes for every TTL past it. A round
+        // cut off early, or answered by a router at the last TTL, means the
+        // path changed, so the next one probes the full range again; a
+        // lost reply at the last TTL is just loss at the destination.
+        let dest_name = dest.to_string();
+        match round.hops.last() {
+            Some(hop) if hop.probes.iter().any(|probe| probe.ip.as_deref() == Some(dest_name.as_str())) => {
+                max_hops = hop.hop;
+                result.hops.truncate(max_hops as usize);
+            }
+            Some(hop) if hop.hop == max_hops && hop.times_ms.is_empty() => {}
+            _ => max_hops = MAX_HOPS,
+        }
+
+        if let Some(names) = &mut names {
+            trace::name_hops(&mut round, names).await;
+        }
+
+        result.record(&round);
+        on_cycle(&result);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::{TraceHop, TraceProbe};
+
+    fn hop(hop: u32, ip: &str, time_ms: Option<f64>) -> TraceHop {
+        let probe = match time_ms {
+            Some(time_ms) => TraceProbe { ip: Some(ip.into()), time_ms: Some(time_ms) },
+            None => TraceProbe::timed_out(),
+        };
+        TraceHop::from_probes(hop, vec![probe])
+    }
+
+    #[tokio::test]
+    async fn rejects_multipath() {
+        let options = MtrOptions {
+            cycles: Some(1),
+            interval: Duration::ZERO,
+            trace: TraceOptions { multipath: Some(4), ..TraceOptions::default() },
+        };
+        let error = run_mtr("192.0.2.1", &options, &DnsOptions::default(), &mut |_| {}).await.unwrap_err();
+        assert!(error.contains("trace --multipath"));
+    }
+
+    #[test]
+    fn hop_stats_track_latency_and_loss() {
+        let mut stats = HopStats::default();
+        for time_ms in [Some(20.0), None, Some(10.0), Some(30.0)] {
+            stats.record(time_ms);
+        }
+
+        assert_eq!((stats.sent, stats.received), (4, 3));
+        assert_eq!(stats.loss, 25.0);
+        assert_eq!(stats.last_ms, Some(30.0));
+        assert_eq!(stats.avg_ms, 20.0);
+        assert_eq!(stats.best_ms, 10.0);
+        assert_eq!(stats.worst_ms, 30.0);
+        assert!((stats.stddev_ms - (200.0f64 / 3.0).sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn hop_stats_without_replies_are_all_loss() {
+        let mut stats = HopStats::default();
+        stats.record(None);
+        stats.record(None);
+
+        assert_eq!(stats.loss, 100.0);
+        assert_eq!(stats.last_ms, None);
+        assert_eq!(stats.stddev_ms, 0.0);
+    }
+
+    #[test]
+    fn rounds_fold_into_rows_per_hop() {
+        let mut result = MtrResult::default();
+        result.record(&TraceResult { hops: vec![hop(1, "*", None), hop(2, "10.0.0.2", Some(2.0))] });
+        result.record(&TraceResult { hops: vec![hop(1, "10.0.0.1", Some(1.0)), hop(2, "10.0.0.3", Some(4.0))] });
+        result.record_failure("Send error: Network is unreachable".into());
+
+        assert_eq!(result.cycles, 3);
+        assert_eq!(result.hops.len(), 2);
+        // A hop takes the first address that answered it
+        assert_eq!(result.hops[0].ip, "10.0.0.1");
+        assert_eq!(result.hops[1].ip, "10.0.0.2");
+        assert_eq!((result.hops[0].sent, result.hops[0].received), (3, 1));
+        assert_eq!((result.hops[1].sent, result.hops[1].received), (3, 2));
+        assert_eq!(result.hops[1].avg_ms, 3.0);
+        assert!(result.last_error.is_some());
+    }
+}
@@ -0,0 +1,542 @@
+// StaxPing — Unified Network Diagnostics
+// Copyright (c) 2026 StaxDash
+//
+// This source code is provided under the StaxPing Source‑Available License & EULA.
+// You may view, modify, and redistribute this code for personal or internal use.
+// Commercial use of any kind requires explicit written permission from StaxDash.
+//
+// Full license text available in LICENSE and EULA.md.
+
+use clap::ValueEnum;
+use std::io::IsTerminal;
+
+use crate::delegation::{DelegationTrace, ProbeOutcome};
+use crate::dns::{DnsComparison, DnsResult, DnsStatus, FcrDns, ReverseLookup};
+use crate::http::HttpResult;
+use crate::mtr::MtrResult;
+use crate::ping::{AddressPing, PingReply, PingResult};
+use crate::report::{DualStack, FamilyRun, Report, Section};
+use crate::trace::TraceResult;
+
+/// How a report is written to stdout
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable sections
+    #[default]
+    Text,
+    /// A single JSON document
+    Json,
+}
+
+/// Simple aligned key/value printer
+pub fn kv(label: &str, value: impl std::fmt::Display) {
+    println!("  {:<12} {}", label, value);
+}
+
+/// Writes the report in the requested format.
+pub fn print_report(report: &Report, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => print_text(report),
+        OutputFormat::Json => print_json(report),
+    }
+}
+
+/// Prints what comes before the reply stream of a continuous ping.
+pub fn print_live_start(target: &str, format: OutputFormat) {
+    if format == OutputFormat::Text {
+        print_banner(target);
+        println!("=== Ping ==============================");
+    }
+}
+
+/// Prints one reply of a continuous ping: a text line or a JSON line.
+pub fn print_live_reply(reply: &PingReply, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => match (reply.rtt_ms, reply.ttl) {
+            (Some(rtt), Some(ttl)) => println!("  seq={:<5} ttl={:<3} time={:.2} ms", reply.seq, ttl, rtt),
+            (Some(rtt), None) => println!("  seq={:<5} time={:.2} ms", reply.seq, rtt),
+            (None, _) => println!("  seq={:<5} timeout", reply.seq),
+        },
+        OutputFormat::Json => {
+            if let Ok(json) = serde_json::to_string(reply) {
+                println!("{}", json);
+            }
+        }
+    }
+}
+
+/// Prints the closing summary of a continuous ping.
+///
+/// JSON output ends the reply stream with the full report on one line.
+pub fn print_live_summary(report: &Report, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => {
+            println!("\n--- {} ping statistics ---", report.target);
+            if let Some(section) = &report.ping {
+                print_section(section, "Ping error:", print_ping);
+            }
+        }
+        OutputFormat::Json => match serde_json::to_string(report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize report: {}", e),
+        },
+    }
+}
+
+/// Redraws the MTR table after a round; only text output on a terminal
+/// is refreshed, everything else waits for the final report.
+pub fn print_live_mtr(target: &str, result: &MtrResult, format: OutputFormat) {
+    if format == OutputFormat::Text && std::io::stdout().is_terminal() {
+        print!("\x1b[H\x1b[2J");
+        print_banner(target);
+        println!("=== MTR ({} rounds) {}", result.cycles, "=".repeat(18));
+        print_mtr(result);
+    }
+}
+
+/// Clears the live MTR table so the final report replaces it.
+pub fn clear_live_mtr(format: OutputFormat) {
+    if format == OutputFormat::Text && std::io::stdout().is_terminal() {
+        print!("\x1b[H\x1b[2J");
+    }
+}
+
+fn print_json(report: &Report) {
+    match serde_json::to_string_pretty(report) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Failed to serialize report: {}", e),
+    }
+}
+
+/// Top-level banner
+fn print_banner(target: &str) {
+    println!("========================================");
+    println!("  StaxPing v0.1.0 — Network Diagnostics");
+    println!("  Target: {}", target);
+    println!("========================================\n");
+}
+
+fn print_text(report: &Report) {
+    print_banner(&report.target);
+
+    let mut first = true;
+
+    if let Some(section) = &report.dns {
+        print_header("DNS", &mut first);
+        print_section(section, "DNS error:", print_dns);
+    }
+
+    if let Some(section) = &report.dns_comparison {
+        print_header("DNS Comparison", &mut first);
+        print_section(section, "DNS error:", print_dns_comparison);
+    }
+
+    if let Some(section) = &report.dns_delegation {
+        print_header("DNS Delegation", &mut first);
+        print_section(section, "DNS error:", print_delegation);
+    }
+
+    if let Some(section) = &report.ping {
+        print_header("Ping", &mut first);
+        print_section(section, "Ping error:", print_ping);
+    }
+
+    if let Some(section) = &report.ping_all {
+        print_header("Ping", &mut first);
+        print_section(section, "Ping error:", print_ping_all);
+    }
+
+    if let Some(section) = &report.http {
+        print_header("HTTP", &mut first);
+        print_section(section, "HTTP error:", print_http);
+    }
+
+    if let Some(section) = &report.trace {
+        print_header("Traceroute", &mut first);
+        print_section(section, "Trace error:", print_trace);
+    }
+
+    if let Some(section) = &report.mtr {
+        print_header("MTR", &mut first);
+        print_section(section, "MTR error:", print_mtr);
+    }
+
+    if let Some(dual_stack) = &report.dual_stack {
+        print_header("Dual Stack", &mut first);
+        print_dual_stack(dual_stack);
+    }
+}
+
+/// Prints a `=== Name ====` section header, separated from the previous section
+fn print_header(name: &str, first: &mut bool) {
+    if !*first {
+        println!();
+    }
+    *first = false;
+    println!("=== {} {}", name, "=".repeat(34 - name.len()));
+}
+
+fn print_section<T>(section: &Section<T>, error_label: &str, print: fn(&T)) {
+    if let Some(result) = &section.result {
+        print(result);
+    }
+    if let Some(error) = &section.error {
+        kv(error_label, error);
+    }
+}
+
+fn print_dns(result: &DnsResult) {
+    if result.resolver != "system" {
+        kv("Resolver:", &result.resolver);
+    }
+    if let Some(handshake_ms) = result.handshake_ms {
+        kv("Transport:", format!("{} (handshake ~{} ms)", result.transport.describe(), handshake_ms));
+    }
+    if !result.ipv4.is_empty() {
+        kv("IPv4:", format!("{:?}{}", result.ipv4, address_timing(result, "A")));
+    }
+    if !result.ipv6.is_empty() {
+        kv("IPv6:", format!("{:?}{}", result.ipv6, address_timing(result, "AAAA")));
+    }
+    kv("Lookup:", format!("{} ms", result.lookup_ms));
+
+    for set in &result.answers {
+        let label = format!("{}:", set.record_type);
+        if let Some(dnssec) = &set.dnssec {
+            let value = match &dnssec.detail {
+                Some(detail) => format!("{} {} ({})", set.record_type, dnssec.status, detail),
+                None => format!("{} {}", set.record_type, dnssec.status),
+            };
+            kv("DNSSEC:", value);
+        }
+        if set.status != DnsStatus::NoError {
+            let reason = match (set.status, &set.error) {
+                (DnsStatus::Error, Some(error)) => error.as_str(),
+                (status, _) => status.describe(),
+            };
+            kv(&label, format!("{} ({})  {} ms", set.status, reason, set.lookup_ms));
+            continue;
+        }
+
+        // A and AAAA are already summarised above
+        if set.record_type == "A" || set.record_type == "AAAA" {
+            continue;
+        }
+
+        for (i, record) in set.records.iter().enumerate() {
+            let label = if i == 0 { label.as_str() } else { "" };
+            let value = if record.record_type == set.record_type {
+                format!("{}  (ttl {}s)", record.data, record.ttl)
+            } else {
+                format!("{} {}  (ttl {}s)", record.record_type, record.data, record.ttl)
+            };
+            kv(label, value);
+        }
+    }
+
+    print_reverse(&result.reverse);
+}
+
+/// TTL and query time of the A or AAAA set, e.g. "  (ttl 300s, 12 ms)"
+fn address_timing(result: &DnsResult, record_type: &str) -> String {
+    match result.answers.iter().find(|set| set.record_type == record_type) {
+        Some(set) => match set.ttl {
+            Some(ttl) => format!("  (ttl {}s, {} ms)", ttl, set.lookup_ms),
+            None => format!("  ({} ms)", set.lookup_ms),
+        },
+        // IP targets are not looked up
+        None => String::new(),
+    }
+}
+
+/// One line per address: its PTR names and whether they resolve back to it
+fn print_reverse(reverse: &[ReverseLookup]) {
+    for (i, lookup) in reverse.iter().enumerate() {
+        let label = if i == 0 { "Reverse:" } else { "" };
+        let value = match lookup.fcrdns {
+            FcrDns::NoPtr if lookup.status == DnsStatus::NoError => format!("{} -> no PTR", lookup.ip),
+            FcrDns::NoPtr => {
+                let reason = match (lookup.status, &lookup.error) {
+                    (DnsStatus::Error, Some(error)) => error.as_str(),
+                    (status, _) => status.describe(),
+                };
+                format!("{} -> {} ({})", lookup.ip, lookup.status, reason)
+            }
+            FcrDns::Confirmed => format!("{} -> {}  (FCrDNS confirmed)", lookup.ip, lookup.names.join(", ")),
+            FcrDns::Mismatch => format!("{} -> {}  (FCrDNS mismatch)", lookup.ip, lookup.names.join(", ")),
+        };
+        kv(label, value);
+    }
+}
+
+fn print_dns_comparison(comparison: &DnsComparison) {
+    println!(
+        "    {:<24} {:<6} {:<40} {:>7} {:>8}",
+        "Resolver", "Type", "Answers", "TTL", "Time"
+    );
+
+    for row in &comparison.rows {
+        let result = match (&row.result, &row.error) {
+            (Some(result), _) => result,
+            (None, error) => {
+                let error = error.as_deref().unwrap_or("no answer");
+                println!("    {:<24} {}", row.resolver, error);
+                continue;
+            }
+        };
+
+        for set in &result.answers {
+            // Flag rows whose answers differ from the other resolvers
+            let disagrees = set.status.answered() && comparison.disagreements.contains(&set.record_type);
+            let marker = if disagrees { "!" } else { " " };
+            let answers = match set.status {
+                DnsStatus::NoError => set.sorted_data().join(", "),
+                status => status.to_string(),
+            };
+            let ttl = set.ttl.map(|ttl| format!("{}s", ttl)).unwrap_or_else(|| "-".into());
+
+            println!(
+                "  {} {:<24} {:<6} {:<40} {:>7} {:>5} ms",
+                marker, row.resolver, set.record_type, answers, ttl, set.lookup_ms
+            );
+        }
+    }
+
+    let answered = comparison.rows.len() - comparison.failed.len();
+    if !comparison.disagreements.is_empty() {
+        kv("Result:", format!("Resolvers disagree on {}", comparison.disagreements.join(", ")));
+    } else if comparison.failed.is_empty() {
+        kv("Result:", "All resolvers agree");
+    } else if answered > 1 {
+        kv("Result:", "The resolvers that answered agree");
+    } else {
+        kv("Result:", "Too few resolvers answered to compare");
+    }
+    if !comparison.failed.is_empty() {
+        kv("Failed:", comparison.failed.join(", "));
+    }
+}
+
+fn print_delegation(trace: &DelegationTrace) {
+    for (i, step) in trace.steps.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        kv("Zone:", &step.zone);
+
+        for probe in &step.servers {
+            let time = probe.time_ms.map(|ms| format!("{} ms", ms)).unwrap_or_else(|| "-".into());
+            let detail = probe.detail.as_deref().unwrap_or("");
+            let outcome = match probe.outcome {
+                ProbeOutcome::Lame => format!("LAME ({})", detail),
+                ProbeOutcome::Unreachable => format!("unreachable ({})", detail),
+                ProbeOutcome::Referral | ProbeOutcome::Answer => detail.to_string(),
+            };
+            println!("    {:<28} {:<15} {:>7}  {}", probe.name, probe.address, time, outcome);
+        }
+
+        if let Some(referral) = &step.referral {
+            for (i, nameserver) in referral.nameservers.iter().enumerate() {
+                let label = if i == 0 { "NS:" } else { "" };
+                let glue = if nameserver.glue.is_empty() {
+                    "no glue".to_string()
+                } else {
+                    nameserver.glue.join(", ")
+                };
+                kv(label, format!("{} ({})", nameserver.name, glue));
+            }
+        }
+    }
+
+    if let Some(error) = &trace.error {
+        kv("Result:", error);
+        return;
+    }
+
+    if trace.status != DnsStatus::NoError {
+        kv(&format!("{}:", trace.record_type), format!("{} ({})", trace.status, trace.status.describe()));
+    }
+    for (i, record) in trace.answers.iter().enumerate() {
+        let label = if i == 0 { "Answer:" } else { "" };
+        kv(label, format!("{} {}  (ttl {}s)", record.record_type, record.data, record.ttl));
+    }
+}
+
+fn print_ping(result: &PingResult) {
+    kv("Sent:", result.sent);
+    kv("Received:", result.received);
+    kv("Loss:", format!("{:.1}%", result.loss));
+    kv("Min:", format!("{:.2} ms", result.min_ms));
+    kv("Avg:", format!("{:.2} ms", result.avg_ms));
+    kv("Max:", format!("{:.2} ms", result.max_ms));
+    kv("StdDev:", format!("{:.2} ms", result.stddev_ms));
+    kv("Jitter:", format!("{:.2} ms", result.jitter_ms));
+    kv(
+        "Percentiles:",
+        format!(
+            "p50 {:.2} ms / p90 {:.2} ms / p99 {:.2} ms",
+            result.p50_ms, result.p90_ms, result.p99_ms
+        ),
+    );
+}
+
+/// One row per address, so a dead member of a round-robin set stands out
+fn print_ping_all(pings: &Vec<AddressPing>) {
+    println!(
+        "    {:<39} {:>4} {:>4} {:>6} {:>9} {:>9} {:>9}",
+        "Address", "Sent", "Recv", "Loss", "Min", "Avg", "Max"
+    );
+
+    for ping in pings {
+        match (&ping.result, &ping.error) {
+            (Some(result), _) => println!(
+                "    {:<39} {:>4} {:>4} {:>5.1}% {:>6.2} ms {:>6.2} ms {:>6.2} ms",
+                ping.ip, result.sent, result.received, result.loss, result.min_ms, result.avg_ms, result.max_ms
+            ),
+            (None, error) => println!("    {:<39} {}", ping.ip, error.as_deref().unwrap_or("no result")),
+        }
+    }
+}
+
+fn print_http(result: &HttpResult) {
+    kv("Status:", result.status);
+    kv("Time:", format!("{} ms", result.time_ms));
+    kv("Final URL:", &result.final_url);
+    if let Some(remote_addr) = &result.remote_addr {
+        kv("Address:", remote_addr);
+    }
+}
+
+/// IPv4 and IPv6 side by side, then the differences and any errors
+fn print_dual_stack(dual_stack: &DualStack) {
+    let families = [("IPv4", &dual_stack.ipv4), ("IPv6", &dual_stack.ipv6)];
+
+    println!("    {:<8} {:<40} IPv6", "", "IPv4");
+    let row = |label: &str, cell: fn(&FamilyRun) -> String| {
+        println!("    {:<8} {:<40} {}", label, cell(&dual_stack.ipv4), cell(&dual_stack.ipv6));
+    };
+
+    row("Address", |run| run.address.clone().unwrap_or_else(|| "none".into()));
+    row("Ping", |run| match run.ping.as_ref().and_then(|ping| ping.result.as_ref()) {
+        Some(result) if result.received > 0 => format!("{:.2} ms avg, {:.1}% loss", result.avg_ms, result.loss),
+        Some(result) => format!("{:.1}% loss", result.loss),
+        None => "-".into(),
+    });
+    row("HTTP", |run| match run.http.as_ref().and_then(|http| http.result.as_ref()) {
+        Some(result) => format!("{} in {} ms", result.status, result.time_ms),
+        None => "-".into(),
+    });
+    if families.iter().any(|(_, run)| run.trace.is_some()) {
+        row("Trace", |run| match run.trace.as_ref().and_then(|trace| trace.result.as_ref()) {
+            Some(result) => format!("{} hops", result.hops.len()),
+            None => "-".into(),
+        });
+    }
+
+    let avg = |run: &FamilyRun| run.ping.as_ref()?.result.as_ref().filter(|r| r.received > 0).map(|r| r.avg_ms);
+    if let (Some(v4), Some(v6)) = (avg(&dual_stack.ipv4), avg(&dual_stack.ipv6)) {
+        kv("Ping diff:", format!("IPv6 {:+.2} ms vs IPv4", v6 - v4));
+    }
+    let time = |run: &FamilyRun| run.http.as_ref()?.result.as_ref().map(|r| r.time_ms as i128);
+    if let (Some(v4), Some(v6)) = (time(&dual_stack.ipv4), time(&dual_stack.ipv6)) {
+        kv("HTTP diff:", format!("IPv6 {:+} ms vs IPv4", v6 - v4));
+    }
+
+    for (family, run) in families {
+        let errors = [
+            ("", run.error.as_ref()),
+            ("ping", run.ping.as_ref().and_then(|ping| ping.error.as_ref())),
+            ("HTTP", run.http.as_ref().and_then(|http| http.error.as_ref())),
+            ("trace", run.trace.as_ref().and_then(|trace| trace.error.as_ref())),
+        ];
+        for (stage, error) in errors {
+            if let Some(error) = error {
+                let label = format!("{} {}", family, stage);
+                kv(&format!("{}:", label.trim_end()), error);
+            }
+        }
+    }
+}
+
+fn print_trace(result: &TraceResult) {
+    for hop in &result.hops {
+        // Probes print in order, "*" for a timeout, with the address
+        // repeated wherever a different router answered (multipath runs
+        // list their next hops below instead)
+        let mut shown = &hop.ip;
+        let times: Vec<String> = hop.probes.iter()
+            .map(|probe| match (&probe.ip, probe.time_ms) {
+                (Some(ip), Some(t)) if ip != shown && hop.next_hops.is_empty() => {
+                    shown = ip;
+                    format!("{}  {:.2} ms", ip, t)
+                }
+                (_, Some(t)) => format!("{:.2} ms", t),
+                _ => "*".to_string(),
+            })
+            .collect();
+
+        // Named hops read like traceroute's "name (address)"
+        let label = if hop.host != hop.ip {
+            format!("{} ({})", hop.host, hop.ip)
+        } else {
+            hop.ip.clone()
+        };
+
+        if hop.times_ms.is_empty() {
+            println!("  {:>2}  {}", hop.hop, times.join("  "));
+        } else {
+            println!(
+                "  {:>2}  {:<15}  {}",
+                hop.hop,
+                label,
+                times.join("  ")
+            );
+        }
+
+        // Other next hops a load balancer spread the flows over
+        for next_hop in hop.next_hops.iter().filter(|next_hop| **next_hop != hop.ip) {
+            println!("      {}", next_hop);
+        }
+    }
+}
+
+/// One row per hop with its loss and latency over every round
+fn print_mtr(result: &MtrResult) {
+    println!(
+        "  {:>3}  {:<32} {:>6} {:>4} {:>4} {:>8} {:>8} {:>8} {:>8} {:>8}",
+        "Hop", "Host", "Loss", "Sent", "Recv", "Last", "Avg", "Best", "Worst", "StDev"
+    );
+
+    for hop in &result.hops {
+        let label = if hop.host != hop.ip {
+            format!("{} ({})", hop.host, hop.ip)
+        } else {
+            hop.ip.clone()
+        };
+
+        if hop.received == 0 {
+            println!(
+                "  {:>3}  {:<32} {:>5.1}% {:>4} {:>4}",
+                hop.hop, label, hop.loss, hop.sent, hop.received
+            );
+        } else {
+            println!(
+                "  {:>3}  {:<32} {:>5.1}% {:>4} {:>4} {:>8.2} {:>8.2} {:>8.2} {:>8.2} {:>8.2}",
+                hop.hop,
+                label,
+                hop.loss,
+                hop.sent,
+                hop.received,
+                hop.last_ms.unwrap_or_default(),
+                hop.avg_ms,
+                hop.best_ms,
+                hop.worst_ms,
+                hop.stddev_ms
+            );
+        }
+    }
+
+    if let Some(error) = &result.last_error {
+        kv("Last error:", error);
+    }
+}
@@ -7,12 +7,16 @@
 //
 // Full license text available in LICENSE and EULA.md.
 
-use surge_ping::{Client, Config, PingIdentifier, PingSequence};
+use surge_ping::{Client, Config, IcmpPacket, PingIdentifier, PingSequence, ICMP};
 use std::net::IpAddr;
+use std::process::Stdio;
 use std::time::{Duration, Instant};
 use rand::Rng;
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
 use tokio::process::Command;
 
+#[derive(Serialize, Debug, Clone)]
 pub struct PingResult {
     pub sent: u32,
     pub received: u32,
@@ -20,113 +24,414 @@ pub struct PingResult {
     pub min_ms: f64,
     pub avg_ms: f64,
     pub max_ms: f64,
+    pub stddev_ms: f64,
+    pub jitter_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
 }
 
-pub async fn run_ping(ip: &str) -> Result<PingResult, String> {
+/// A single echo reply, or a timeout when `rtt_ms` is `None`
+#[derive(Serialize, Debug, Clone)]
+pub struct PingReply {
+    pub seq: u16,
+    pub ttl: Option<u8>,
+    pub rtt_ms: Option<f64>,
+}
+
+/// Probe settings for a ping run
+#[derive(Debug, Clone)]
+pub struct PingOptions {
+    /// Number of echo requests to send
+    pub count: u32,
+    /// Pause between requests
+    pub interval: Duration,
+    /// How long to wait for each reply
+    pub timeout: Duration,
+    /// ICMP payload size in bytes
+    pub size: usize,
+    /// Ignore `count` and keep pinging until Ctrl-C
+    pub continuous: bool,
+}
+
+impl Default for PingOptions {
+    fn default() -> Self {
+        Self {
+            count: 4,
+            interval: Duration::from_millis(500),
+            timeout: Duration::from_secs(2),
+            size: 8,
+            continuous: false,
+        }
+    }
+}
+
+/// Ping statistics for one of several addresses of a target
+#[derive(Serialize, Debug, Clone)]
+pub struct AddressPing {
+    pub ip: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<PingResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub async fn run_ping(ip: &str, options: &PingOptions) -> Result<PingResult, String> {
+    run_ping_live(ip, options, &mut |_| {}).await
+}
+
+/// Pings every address at once, keeping their order.
+pub async fn run_ping_all(ips: &[String], options: &PingOptions) -> Vec<AddressPing> {
+    let handles: Vec<_> = ips
+        .iter()
+        .map(|ip| {
+            let ip = ip.clone();
+            let options = options.clone();
+            tokio::spawn(async move { run_ping(&ip, &options).await })
+        })
+        .collect();
+
+    let mut pings = Vec::new();
+    for (ip, handle) in ips.iter().zip(handles) {
+        let result = handle
+            .await
+            .unwrap_or_else(|e| Err(format!("Ping task failed: {}", e)));
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(e) => (None, Some(e)),
+        };
+        pings.push(AddressPing { ip: ip.clone(), result, error });
+    }
+    pings
+}
+
+/// Pings `ip`, handing every reply to `on_reply` as it arrives.
+///
+/// In continuous mode this only returns once Ctrl-C is pressed.
+pub async fn run_ping_live(
+    ip: &str,
+    options: &PingOptions,
+    on_reply: &mut (dyn FnMut(&PingReply) + Send),
+) -> Result<PingResult, String> {
     // Try surge-ping first
-    match run_raw_ping(ip).await {
-        Ok(result) => return Ok(result),
+    match run_raw_ping(ip, options, on_reply).await {
+        Ok(result) => Ok(result),
         Err(e) => {
             if e.contains("Operation not permitted") {
                 // Fall back to /bin/ping
-                return run_fallback_ping(ip).await;
+                run_fallback_ping(ip, options, on_reply).await
             } else {
-                return Err(e);
+                Err(e)
             }
         }
     }
 }
 
-async fn run_raw_ping(ip: &str) -> Result<PingResult, String> {
+async fn run_raw_ping(
+    ip: &str,
+    options: &PingOptions,
+    on_reply: &mut (dyn FnMut(&PingReply) + Send),
+) -> Result<PingResult, String> {
     let ip: IpAddr = ip.parse().map_err(|_| format!("Invalid IP address: {}", ip))?;
 
-    let client = Client::new(&Config::default())
+    let kind = if ip.is_ipv6() { ICMP::V6 } else { ICMP::V4 };
+    let client = Client::new(&Config::builder().kind(kind).build())
         .map_err(|e| format!("Ping client init failed: {}", e))?;
 
     let identifier = PingIdentifier(rand::thread_rng().gen());
     let mut sequence = PingSequence(0);
 
     let mut pinger = client.pinger(ip, identifier).await;
+    pinger.timeout(options.timeout);
+
+    let payload = make_payload(options.size);
 
     let mut times = Vec::new();
     let mut received = 0u32;
-    let sent = 4u32;
+    let mut sent = 0u32;
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    while options.continuous || sent < options.count {
+        if sent > 0 {
+            tokio::select! {
+                _ = tokio::time::sleep(options.interval) => {}
+                _ = &mut ctrl_c, if options.continuous => break,
+            }
+        }
 
-    for _ in 0..sent {
         let start = Instant::now();
 
-        let payload = b"staxping";
-        let result = pinger.ping(sequence, payload).await;
-        sequence.0 += 1;
+        let result = tokio::select! {
+            result = pinger.ping(sequence, &payload) => result,
+            _ = &mut ctrl_c, if options.continuous => break,
+        };
 
-        match result {
-            Ok((_packet, _addr)) => {
-                received += 1;
-                let elapsed = start.elapsed().as_secs_f64() * 1000.0;
-                times.push(elapsed);
-            }
-            Err(_) => {}
+        let mut reply = PingReply {
+            seq: sequence.0,
+            ttl: None,
+            rtt_ms: None,
+        };
+
+        sent += 1;
+        sequence.0 = sequence.0.wrapping_add(1);
+
+        if let Ok((packet, _)) = result {
+            received += 1;
+            let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+            times.push(elapsed);
+
+            reply.rtt_ms = Some(elapsed);
+            // surge-ping does not expose the hop limit of ICMPv6 replies
+            reply.ttl = match packet {
+                IcmpPacket::V4(packet) => packet.get_ttl(),
+                IcmpPacket::V6(_) => None,
+            };
         }
 
-        tokio::time::sleep(Duration::from_millis(500)).await;
+        on_reply(&reply);
+    }
+
+    if sent == 0 {
+        return Err("Interrupted before any echo request was sent".into());
     }
 
     Ok(calculate_stats(sent, received, times))
 }
 
-async fn run_fallback_ping(ip: &str) -> Result<PingResult, String> {
-    let output = Command::new("/bin/ping")
-        .arg("-c")
-        .arg("4")
+/// Builds a payload of `size` bytes by repeating the "staxping" tag
+fn make_payload(size: usize) -> Vec<u8> {
+    b"staxping".iter().copied().cycle().take(size).collect()
+}
+
+async fn run_fallback_ping(
+    ip: &str,
+    options: &PingOptions,
+    on_reply: &mut (dyn FnMut(&PingReply) + Send),
+) -> Result<PingResult, String> {
+    // /bin/ping takes seconds; -W only accepts whole seconds on older iputils
+    let interval = format!("{:.3}", options.interval.as_secs_f64());
+    let timeout = options.timeout.as_secs_f64().ceil().max(1.0).to_string();
+
+    let mut command = Command::new("/bin/ping");
+    if !options.continuous {
+        command.arg("-c").arg(options.count.to_string());
+    }
+
+    let mut child = command
+        .arg("-i")
+        .arg(interval)
+        .arg("-W")
+        .arg(timeout)
+        .arg("-s")
+        .arg(options.size.to_string())
         .arg(ip)
-        .output()
-        .await
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
         .map_err(|e| format!("Failed to run /bin/ping: {}", e))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stdout = child.stdout.take().ok_or("Failed to read /bin/ping output")?;
+    // Read alongside stdout so a chatty ping cannot fill the pipe and stall
+    let mut stderr = child.stderr.take().ok_or("Failed to read /bin/ping output")?;
+    let errors = tokio::spawn(async move {
+        let mut errors = String::new();
+        let _ = stderr.read_to_string(&mut errors).await;
+        errors
+    });
+    let mut lines = BufReader::new(stdout).lines();
+    let mut output = String::new();
+    let mut probes: Option<Probes> = None;
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    // Ctrl-C in a terminal also reaches /bin/ping, which then prints its
+    // summary and exits; only kill it if it is still running after that.
+    let mut deadline = None;
+
+    loop {
+        let grace = async {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => {
+                    if let Some(reply) = parse_reply_line(&line) {
+                        let probes = probes.get_or_insert_with(|| Probes::new(&output));
+                        for reply in probes.track(reply) {
+                            on_reply(&reply);
+                        }
+                    }
+                    output.push_str(&line);
+                    output.push('\n');
+                }
+                _ => break,
+            },
+            _ = &mut ctrl_c, if options.continuous && deadline.is_none() => {
+                deadline = Some(tokio::time::Instant::now() + Duration::from_secs(1));
+            }
+            _ = grace => {
+                let _ = child.start_kill();
+                break;
+            }
+        }
+    }
+
+    let status = child.wait().await;
+
+    // ping also exits non-zero when every echo is lost, but still prints
+    // its summary; without one it rejected the options or never started
+    if !status.is_ok_and(|status| status.success()) && !output.contains("packets transmitted") {
+        let errors = errors.await.unwrap_or_default();
+        let reason = errors.lines().next().unwrap_or("no output").trim();
+        return Err(format!("/bin/ping failed: {}", reason));
+    }
+
+    // Probes lost after the last reply only show up in the final count
+    let mut probes = probes.unwrap_or_else(|| Probes::new(&output));
+    let count = if options.continuous {
+        transmitted(&output).unwrap_or(probes.sent)
+    } else {
+        options.count
+    };
+    for reply in probes.finish(count) {
+        on_reply(&reply);
+    }
+    parse_ping_output(&output, probes.sent)
+}
+
+/// Turns /bin/ping's reply lines into one reply or timeout per probe, and
+/// counts the probes: icmp_seq is only 16 bits and wraps on long runs.
+struct Probes {
+    next_seq: u16,
+    sent: u32,
+}
+
+impl Probes {
+    /// iputils numbers probes from 1, BSD and BusyBox ping from 0; only
+    /// iputils' header says "bytes of data"
+    fn new(header: &str) -> Self {
+        let first_seq = if header.contains("bytes of data") { 1 } else { 0 };
+        Self { next_seq: first_seq, sent: 0 }
+    }
+
+    /// Timeouts for the probes skipped before `reply`, then `reply` itself.
+    /// Duplicate and late replies to earlier probes give nothing.
+    fn track(&mut self, reply: PingReply) -> Vec<PingReply> {
+        let skipped = reply.seq.wrapping_sub(self.next_seq);
+        if skipped >= 0x8000 {
+            return Vec::new();
+        }
+
+        let mut replies: Vec<PingReply> = (0..skipped)
+            .map(|i| PingReply { seq: self.next_seq.wrapping_add(i), ttl: None, rtt_ms: None })
+            .collect();
+        self.next_seq = reply.seq.wrapping_add(1);
+        self.sent += skipped as u32 + 1;
+        replies.push(reply);
+        replies
+    }
+
+    /// Timeouts for the probes after the last reply, up to `count` in all
+    fn finish(&mut self, count: u32) -> Vec<PingReply> {
+        let mut replies = Vec::new();
+        while self.sent < count {
+            replies.push(PingReply { seq: self.next_seq, ttl: None, rtt_ms: None });
+            self.next_seq = self.next_seq.wrapping_add(1);
+            self.sent += 1;
+        }
+        replies
+    }
+}
+
+/// The probe count from ping's summary line
+fn transmitted(output: &str) -> Option<u32> {
+    let line = output.lines().find(|line| line.contains("packets transmitted"))?;
+    line.split_whitespace().next()?.parse().ok()
+}
+
+/// Parses a `/bin/ping` reply line into a [`PingReply`].
+///
+/// Example: "64 bytes from 1.1.1.1: icmp_seq=1 ttl=57 time=12.3 ms"
+fn parse_reply_line(line: &str) -> Option<PingReply> {
+    let field = |name: &str| {
+        line.split_whitespace()
+            .find_map(|part| part.strip_prefix(name))
+            .map(|value| value.trim_end_matches("ms").to_string())
+    };
+
+    let seq = field("icmp_seq=").or_else(|| field("seq="))?.parse().ok()?;
 
-    parse_ping_output(&stdout)
+    Some(PingReply {
+        seq,
+        ttl: field("ttl=").and_then(|ttl| ttl.parse().ok()),
+        rtt_ms: field("time=").and_then(|time| time.parse().ok()),
+    })
 }
 
-fn parse_ping_output(output: &str) -> Result<PingResult, String> {
-    let mut sent = 4;
-    let mut received = 0;
-    let mut min = 0.0;
-    let mut avg = 0.0;
-    let mut max = 0.0;
+fn parse_ping_output(output: &str, count: u32) -> Result<PingResult, String> {
+    let mut sent = count;
+    let mut received = None;
+    let mut times = Vec::new();
+    let mut summary = None;
 
     for line in output.lines() {
         if line.contains("packets transmitted") {
             // Example: "4 packets transmitted, 4 received, 0% packet loss"
-            let parts: Vec<&str> = line.split(',').collect();
-            sent = parts[0].trim().split(' ').next().unwrap().parse().unwrap_or(4);
-            received = parts[1].trim().split(' ').next().unwrap().parse().unwrap_or(0);
+            let mut parts = line.split(',').map(|part| part.split_whitespace().next().unwrap_or(""));
+            sent = parts.next().and_then(|part| part.parse().ok()).unwrap_or(count);
+            received = Some(parts.next().and_then(|part| part.parse().ok()).unwrap_or(0));
         }
 
         if line.contains("min/avg/max") {
             // Example: "rtt min/avg/max/mdev = 12.345/14.567/16.789/0.123 ms"
-            let stats = line.split('=').nth(1).unwrap().trim();
-            let values: Vec<&str> = stats.split('/').collect();
-            min = values[0].parse().unwrap_or(0.0);
-            avg = values[1].parse().unwrap_or(0.0);
-            max = values[2].parse().unwrap_or(0.0);
+            let stats = line.split('=').nth(1).unwrap_or("").trim();
+            let values: Vec<f64> = stats
+                .trim_end_matches("ms")
+                .trim()
+                .split('/')
+                .map(|v| v.parse().unwrap_or(0.0))
+                .collect();
+            summary = Some(values);
+        }
+
+        if let Some(time) = parse_reply_line(line).and_then(|reply| reply.rtt_ms) {
+            times.push(time);
         }
     }
 
-    let loss = ((sent - received) as f32 / sent as f32) * 100.0;
+    let has_replies = !times.is_empty();
+    // Killed before its summary: count the replies themselves
+    let received = received.unwrap_or(times.len() as u32);
+    let mut result = calculate_stats(sent, received, times);
 
-    Ok(PingResult {
-        sent,
-        received,
-        loss,
-        min_ms: min,
-        avg_ms: avg,
-        max_ms: max,
-    })
+    // Quiet or truncated output has no reply lines; use the summary instead
+    if !has_replies {
+        if let Some(values) = summary {
+            let value = |i: usize| values.get(i).copied().unwrap_or(0.0);
+            result.min_ms = value(0);
+            result.avg_ms = value(1);
+            result.max_ms = value(2);
+            result.stddev_ms = value(3);
+        }
+    }
+
+    Ok(result)
 }
 
 fn calculate_stats(sent: u32, received: u32, times: Vec<f64>) -> PingResult {
-    let loss = ((sent - received) as f32 / sent as f32) * 100.0;
+    // Nothing sent, e.g. a continuous run stopped before its first echo
+    let loss = match sent {
+        0 => 0.0,
+        _ => (sent.saturating_sub(received) as f32 / sent as f32) * 100.0,
+    };
 
     let (min_ms, avg_ms, max_ms) = if !times.is_empty() {
         let min = times.iter().cloned().fold(f64::INFINITY, f64::min);
@@ -137,6 +442,22 @@ fn calculate_stats(sent: u32, received: u32, times: Vec<f64>) -> PingResult {
         (0.0, 0.0, 0.0)
     };
 
+    // Population standard deviation, the same figure ping reports as mdev
+    let stddev_ms = if !times.is_empty() {
+        let variance = times.iter().map(|t| (t - avg_ms).powi(2)).sum::<f64>() / times.len() as f64;
+        variance.sqrt()
+    } else {
+        0.0
+    };
+
+    // RFC 3550 interarrival jitter over consecutive replies: J += (|D| - J) / 16
+    let jitter_ms = times
+        .windows(2)
+        .fold(0.0, |j, pair| j + ((pair[1] - pair[0]).abs() - j) / 16.0);
+
+    let mut sorted = times;
+    sorted.sort_by(|a, b| a.total_cmp(b));
+
     PingResult {
         sent,
         received,
@@ -144,5 +465,170 @@ fn calculate_stats(sent: u32, received: u32, times: Vec<f64>) -> PingResult {
         min_ms,
         avg_ms,
         max_ms,
+        stddev_ms,
+        jitter_ms,
+        p50_ms: percentile(&sorted, 50.0),
+        p90_ms: percentile(&sorted, 90.0),
+        p99_ms: percentile(&sorted, 99.0),
+    }
+}
+
+/// Nearest-rank percentile of an already sorted slice
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IPUTILS: &str = "\
+PING 1.1.1.1 (1.1.1.1) 56(84) bytes of data.
+64 bytes from 1.1.1.1: icmp_seq=1 ttl=57 time=10.0 ms
+64 bytes from 1.1.1.1: icmp_seq=2 ttl=57 time=20.0 ms
+64 bytes from 1.1.1.1: icmp_seq=4 ttl=57 time=15.0 ms
+
+--- 1.1.1.1 ping statistics ---
+4 packets transmitted, 3 received, 25% packet loss, time 3004ms
+rtt min/avg/max/mdev = 10.000/15.000/20.000/4.082 ms
+";
+
+    const IPUTILS_QUIET: &str = "\
+PING 1.1.1.1 (1.1.1.1) 56(84) bytes of data.
+
+--- 1.1.1.1 ping statistics ---
+4 packets transmitted, 4 received, 0% packet loss, time 3004ms
+rtt min/avg/max/mdev = 11.100/12.200/13.300/0.400 ms
+";
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn stats_over_replies() {
+        let result = calculate_stats(4, 3, vec![10.0, 20.0, 15.0]);
+
+        assert_eq!(result.loss, 25.0);
+        assert_eq!((result.min_ms, result.avg_ms, result.max_ms), (10.0, 15.0, 20.0));
+        assert!(close(result.stddev_ms, (50.0f64 / 3.0).sqrt()));
+        // J = 10/16, then J += (5 - J) / 16
+        assert!(close(result.jitter_ms, 0.625 + (5.0 - 0.625) / 16.0));
+        assert_eq!((result.p50_ms, result.p90_ms, result.p99_ms), (15.0, 20.0, 20.0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn stats_without_replies() {
+        let result = calculate_stats(4, 0, Vec::new());
+        assert_eq!(result.loss, 100.0);
+        assert_eq!((result.min_ms, result.max_ms, result.stddev_ms, result.jitter_ms), (0.0, 0.0, 0.0, 0.0));
+
+        // A continuous run stopped before its first echo
+        assert_eq!(calculate_stats(0, 0, Vec::new()).loss, 0.0);
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let sorted: Vec<f64> = (1..=10).map(f64::from).collect();
+        assert_eq!(percentile(&sorted, 50.0), 5.0);
+        assert_eq!(percentile(&sorted, 90.0), 9.0);
+        assert_eq!(percentile(&sorted, 99.0), 10.0);
+        assert_eq!(percentile(&sorted, 100.0), 10.0);
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+
+        assert_eq!(percentile(&[7.0], 99.0), 7.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+
+    #[test]
+    fn parses_iputils_output() {
+        let result = parse_ping_output(IPUTILS, 4).unwrap();
+        assert_eq!((result.sent, result.received), (4, 3));
+        assert_eq!(result.loss, 25.0);
+        assert_eq!((result.min_ms, result.avg_ms, result.max_ms), (10.0, 15.0, 20.0));
+        assert_eq!(result.p50_ms, 15.0);
+    }
+
+    #[test]
+    fn quiet_output_falls_back_to_the_summary() {
+        let result = parse_ping_output(IPUTILS_QUIET, 4).unwrap();
+        assert_eq!((result.sent, result.received), (4, 4));
+        assert_eq!((result.min_ms, result.avg_ms, result.max_ms), (11.1, 12.2, 13.3));
+        assert_eq!(result.stddev_ms, 0.4);
+    }
+
+    #[test]
+    fn output_without_summary_counts_every_echo_as_lost() {
+        let result = parse_ping_output("PING 1.1.1.1 (1.1.1.1) 56(84) bytes of data.\n", 3).unwrap();
+        assert_eq!((result.sent, result.received), (3, 0));
+        assert_eq!(result.loss, 100.0);
+    }
+
+    #[test]
+    fn output_without_summary_counts_the_replies_it_has() {
+        let output = "PING 1.1.1.1 (1.1.1.1) 56(84) bytes of data.\n64 bytes from 1.1.1.1: icmp_seq=1 ttl=57 time=10.0 ms\n";
+        let result = parse_ping_output(output, 2).unwrap();
+        assert_eq!((result.sent, result.received), (2, 1));
+        assert_eq!(result.loss, 50.0);
+    }
+
+    fn reply(seq: u16) -> PingReply {
+        PingReply { seq, ttl: Some(57), rtt_ms: Some(1.0) }
+    }
+
+    fn seqs(replies: &[PingReply]) -> Vec<(u16, bool)> {
+        replies.iter().map(|reply| (reply.seq, reply.rtt_ms.is_some())).collect()
+    }
+
+    #[test]
+    fn probes_fill_gaps_with_timeouts() {
+        let mut probes = Probes::new("PING 1.1.1.1 (1.1.1.1) 56(84) bytes of data.");
+        assert_eq!(seqs(&probes.track(reply(2))), [(1, false), (2, true)]);
+        assert_eq!(seqs(&probes.track(reply(5))), [(3, false), (4, false), (5, true)]);
+
+        // Duplicates and stragglers are not new probes
+        assert!(probes.track(reply(5)).is_empty());
+        assert!(probes.track(reply(3)).is_empty());
+        assert_eq!(probes.sent, 5);
+
+        assert_eq!(seqs(&probes.finish(7)), [(6, false), (7, false)]);
+        assert_eq!(probes.sent, 7);
+        assert!(probes.finish(7).is_empty());
+    }
+
+    #[test]
+    fn probes_count_past_the_sequence_wrap() {
+        // BusyBox and BSD ping start at seq=0
+        let mut probes = Probes::new("PING 1.1.1.1 (1.1.1.1): 56 data bytes");
+        for seq in 0..=u16::MAX {
+            assert_eq!(probes.track(reply(seq)).len(), 1);
+        }
+        assert_eq!(seqs(&probes.track(reply(1))), [(0, false), (1, true)]);
+        assert_eq!(probes.sent, 65536 + 2);
+    }
+
+    #[test]
+    fn parses_reply_lines() {
+        let reply = parse_reply_line("64 bytes from 1.1.1.1: icmp_seq=3 ttl=57 time=12.3 ms").unwrap();
+        assert_eq!((reply.seq, reply.ttl, reply.rtt_ms), (3, Some(57), Some(12.3)));
+
+        // BusyBox and macOS write "seq=", and some pings "time=12.3ms"
+        let reply = parse_reply_line("64 bytes from 1.1.1.1: seq=0 ttl=57 time=0.088ms").unwrap();
+        assert_eq!((reply.seq, reply.ttl, reply.rtt_ms), (0, Some(57), Some(0.088)));
+
+        let reply = parse_reply_line("64 bytes from fe80::1%eth0: icmp_seq=9 time=1.05 ms").unwrap();
+        assert_eq!((reply.seq, reply.ttl, reply.rtt_ms), (9, None, Some(1.05)));
+    }
+
+    #[test]
+    fn other_lines_are_not_replies() {
+        assert!(parse_reply_line("PING 1.1.1.1 (1.1.1.1) 56(84) bytes of data.").is_none());
+        assert!(parse_reply_line("4 packets transmitted, 3 received, 25% packet loss, time 3004ms").is_none());
+        assert!(parse_reply_line("rtt min/avg/max/mdev = 10.000/15.000/20.000/4.082 ms").is_none());
+    }
+}
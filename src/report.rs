@@ -0,0 +1,472 @@
+// StaxPing — Unified Network Diagnostics
+// Copyright (c) 2026 StaxDash
+//
+// This source code is provided under the StaxPing Source‑Available License & EULA.
+// You may view, modify, and redistribute this code for personal or internal use.
+// Commercial use of any kind requires explicit written permission from StaxDash.
+//
+// Full license text available in LICENSE and EULA.md.
+
+use serde::Serialize;
+use std::net::IpAddr;
+
+use crate::delegation::{self, DelegationOptions, DelegationTrace};
+use crate::dns::{self, DnsComparison, DnsOptions, DnsResult, DnsStatus, IpFamily};
+use crate::http::{self, HttpResult};
+use crate::ping::{self, AddressPing, PingOptions, PingReply, PingResult};
+use crate::mtr::{self, MtrOptions, MtrResult};
+use crate::trace::{self, TraceOptions, TraceResult};
+
+/// Outcome of a single diagnostic stage: either its result or the error it hit.
+#[derive(Serialize, Debug, Clone)]
+pub struct Section<T> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl<T> From<Result<T, String>> for Section<T> {
+    fn from(value: Result<T, String>) -> Self {
+        match value {
+            Ok(result) => Section { result: Some(result), error: None },
+            Err(error) => Section { result: None, error: Some(error) },
+        }
+    }
+}
+
+/// Everything collected during one diagnostic run.
+///
+/// Stages that were not run (disabled, or skipped because an earlier
+/// stage failed) are left as `None`.
+#[derive(Serialize, Debug, Clone)]
+pub struct Report {
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Section<DnsResult>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns_comparison: Option<Section<DnsComparison>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns_delegation: Option<Section<DelegationTrace>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ping: Option<Section<PingResult>>,
+    /// Per-address results of an --all-addresses run, in place of `ping`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ping_all: Option<Section<Vec<AddressPing>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<Section<HttpResult>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Section<TraceResult>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mtr: Option<Section<MtrResult>>,
+    /// Ping, HTTP and trace per address family, in place of the single-stack stages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dual_stack: Option<DualStack>,
+}
+
+/// The stages after DNS, run once over IPv4 and once over IPv6
+#[derive(Serialize, Debug, Clone)]
+pub struct DualStack {
+    pub ipv4: FamilyRun,
+    pub ipv6: FamilyRun,
+}
+
+/// One address family's half of a dual-stack run
+#[derive(Serialize, Debug, Clone)]
+pub struct FamilyRun {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Why the family could not be tested, e.g. no AAAA records
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ping: Option<Section<PingResult>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http: Option<Section<HttpResult>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<Section<TraceResult>>,
+}
+
+impl Report {
+    pub fn new(target: &str) -> Self {
+        Self {
+            target: target.to_string(),
+            dns: None,
+            dns_comparison: None,
+            dns_delegation: None,
+            ping: None,
+            ping_all: None,
+            http: None,
+            trace: None,
+            mtr: None,
+            dual_stack: None,
+        }
+    }
+}
+
+/// Process exit codes, one per failing stage.
+///
+/// Codes start at 3 because clap already exits with 2 on usage errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Ok = 0,
+    DnsFailed = 3,
+    PingFailed = 4,
+    HttpFailed = 5,
+    TraceFailed = 6,
+}
+
+impl Report {
+    /// Maps the report to an exit status. The first failing stage, in run
+    /// order, decides the code.
+    ///
+    /// Ping fails on any error, on 100% loss, or when loss exceeds `max_loss`.
+    /// HTTP fails on request errors and 5xx responses.
+    pub fn exit_status(&self, max_loss: f32) -> ExitStatus {
+        if let Some(dns) = &self.dns {
+            match &dns.result {
+                Some(result) if !result.ipv4.is_empty() || !result.ipv6.is_empty() => {}
+                _ => return ExitStatus::DnsFailed,
+            }
+        }
+
+        // A comparison only fails when no resolver answered at all
+        if let Some(comparison) = &self.dns_comparison {
+            let answered = comparison.result.as_ref().is_some_and(|comparison| {
+                comparison.rows.iter().any(|row| {
+                    row.result
+                        .as_ref()
+                        .is_some_and(|result| !result.ipv4.is_empty() || !result.ipv6.is_empty())
+                })
+            });
+            if !answered {
+                return ExitStatus::DnsFailed;
+            }
+        }
+
+        // A delegation trace fails unless it ends in a positive authoritative answer
+        if let Some(delegation) = &self.dns_delegation {
+            match &delegation.result {
+                Some(trace) if trace.error.is_none() && trace.status == DnsStatus::NoError => {}
+                _ => return ExitStatus::DnsFailed,
+            }
+        }
+
+        // Both families have to work end to end
+        if let Some(dual_stack) = &self.dual_stack {
+            let families = [&dual_stack.ipv4, &dual_stack.ipv6];
+            if families.iter().any(|run| run.address.is_none()) {
+                return ExitStatus::DnsFailed;
+            }
+            if families.iter().any(|run| run.ping.as_ref().is_some_and(|ping| ping_failed(ping, max_loss))) {
+                return ExitStatus::PingFailed;
+            }
+            if families.iter().any(|run| run.http.as_ref().is_some_and(http_failed)) {
+                return ExitStatus::HttpFailed;
+            }
+            if families.iter().any(|run| run.trace.as_ref().is_some_and(|trace| trace.error.is_some())) {
+                return ExitStatus::TraceFailed;
+            }
+        }
+
+        if let Some(ping) = &self.ping {
+            if ping_failed(ping, max_loss) {
+                return ExitStatus::PingFailed;
+            }
+        }
+
+        // Every address has to pass, so one dead backend fails the run
+        if let Some(ping_all) = &self.ping_all {
+            let passed = ping_all.result.as_ref().is_some_and(|pings| {
+                pings.iter().all(|ping| {
+                    ping.result
+                        .as_ref()
+                        .is_some_and(|result| result.received > 0 && result.loss <= max_loss)
+                })
+            });
+            if !passed {
+                return ExitStatus::PingFailed;
+            }
+        }
+
+        if let Some(http) = &self.http {
+            if http_failed(http) {
+                return ExitStatus::HttpFailed;
+            }
+        }
+
+        if let Some(trace) = &self.trace {
+            if trace.error.is_some() {
+                return ExitStatus::TraceFailed;
+            }
+        }
+
+        if let Some(mtr) = &self.mtr {
+            if mtr.error.is_some() {
+                return ExitStatus::TraceFailed;
+            }
+        }
+
+        ExitStatus::Ok
+    }
+}
+
+fn ping_failed(ping: &Section<PingResult>, max_loss: f32) -> bool {
+    !ping
+        .result
+        .as_ref()
+        .is_some_and(|result| result.received > 0 && result.loss <= max_loss)
+}
+
+fn http_failed(http: &Section<HttpResult>) -> bool {
+    http.result.as_ref().is_none_or(|result| result.status >= 500)
+}
+
+/// Settings for the combined diagnostic run
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Trace settings, when traceroute is enabled
+    pub trace: Option<TraceOptions>,
+    /// Ping every resolved address rather than only the first
+    pub all_addresses: bool,
+    /// Run ping, HTTP and trace over both IPv4 and IPv6 and compare them
+    pub dual_stack: bool,
+    pub dns: DnsOptions,
+    pub ping: PingOptions,
+}
+
+/// Runs the combined DNS → ping → HTTP (→ traceroute) diagnostic.
+pub async fn run_diagnostics(target: &str, options: &RunOptions) -> Report {
+    let mut report = Report::new(target);
+
+    let dns_result = match dns::resolve_domain(target, &options.dns).await {
+        Ok(result) => {
+            report.dns = Some(Ok(result.clone()).into());
+            result
+        }
+        Err(e) => {
+            report.dns = Some(Err(e).into());
+            return report;
+        }
+    };
+
+    if options.dual_stack {
+        let (ipv4, ipv6) = tokio::join!(
+            run_family(target, &dns_result, IpFamily::Ipv4, options),
+            run_family(target, &dns_result, IpFamily::Ipv6, options),
+        );
+        report.dual_stack = Some(DualStack { ipv4, ipv6 });
+        return report;
+    }
+
+    // Use the first IPv4 address for ping and traceroute
+    let ip = match first_ip(&dns_result) {
+        Some(ip) => ip,
+        None => {
+            let error = format!("No valid IPs found for ping ({}).", address_statuses(&dns_result));
+            report.ping = Some(Err(error).into());
+            return report;
+        }
+    };
+
+    if options.all_addresses {
+        let ips = all_ips(&dns_result);
+        report.ping_all = Some(Ok(ping::run_ping_all(&ips, &options.ping).await).into());
+    } else {
+        report.ping = Some(ping::run_ping(&ip, &options.ping).await.into());
+    }
+    report.http = Some(http::check_http(target, options.dns.family).await.into());
+
+    if let Some(trace_options) = &options.trace {
+        report.trace = Some(trace::run_trace(&ip, trace_options, &options.dns).await.into());
+    }
+
+    report
+}
+
+/// Runs ping, HTTP and (optionally) trace over a single address family.
+async fn run_family(target: &str, dns_result: &DnsResult, family: IpFamily, options: &RunOptions) -> FamilyRun {
+    let addresses = match family {
+        IpFamily::Ipv4 => &dns_result.ipv4,
+        IpFamily::Ipv6 => &dns_result.ipv6,
+    };
+
+    let Some(ip) = addresses.first().cloned() else {
+        let record_type = family.record_type().to_string();
+        let status = dns_result
+            .answers
+            .iter()
+            .find(|set| set.record_type == record_type)
+            .map(|set| format!("{} {}", record_type, set.status))
+            .unwrap_or_else(|| "target is an IP".to_string());
+        return FamilyRun {
+            address: None,
+            error: Some(format!("No {} address ({})", family, status)),
+            ping: None,
+            http: None,
+            trace: None,
+        };
+    };
+
+    let ping = ping::run_ping(&ip, &options.ping).await;
+    let http = http::check_http(target, Some(family)).await;
+    let trace = match &options.trace {
+        Some(trace_options) => Some(trace::run_trace(&ip, trace_options, &options.dns).await.into()),
+        None => None,
+    };
+
+    FamilyRun {
+        address: Some(ip),
+        error: None,
+        ping: Some(ping.into()),
+        http: Some(http.into()),
+        trace,
+    }
+}
+
+/// Runs only the DNS stage.
+pub async fn run_dns(target: &str, options: &DnsOptions) -> Report {
+    let mut report = Report::new(target);
+    report.dns = Some(dns::resolve_domain(target, options).await.into());
+    report
+}
+
+/// Runs the DNS stage against every configured resolver and compares answers.
+pub async fn run_dns_comparison(target: &str, options: &DnsOptions) -> Report {
+    let mut report = Report::new(target);
+    report.dns_comparison = Some(dns::compare_resolvers(target, options).await.into());
+    report
+}
+
+/// Walks the delegation chain for the target from the root servers down.
+pub async fn run_dns_delegation(target: &str, options: &DelegationOptions) -> Report {
+    let mut report = Report::new(target);
+    report.dns_delegation = Some(delegation::trace_delegation(target, options).await.into());
+    report
+}
+
+/// Runs only the ping stage, resolving the target first if it is a name.
+pub async fn run_ping(target: &str, dns: &DnsOptions, options: &PingOptions) -> Report {
+    run_ping_live(target, dns, options, &mut |_| {}).await
+}
+
+/// Runs the ping stage against every address of the target at once.
+pub async fn run_ping_all(target: &str, dns: &DnsOptions, options: &PingOptions) -> Report {
+    let mut report = Report::new(target);
+    let result = match resolve_all(target, dns).await {
+        Ok(ips) => Ok(ping::run_ping_all(&ips, options).await),
+        Err(e) => Err(e),
+    };
+    report.ping_all = Some(result.into());
+    report
+}
+
+/// Like [`run_ping`], but hands every reply to `on_reply` as it arrives.
+pub async fn run_ping_live(
+    target: &str,
+    dns: &DnsOptions,
+    options: &PingOptions,
+    on_reply: &mut (dyn FnMut(&PingReply) + Send),
+) -> Report {
+    let mut report = Report::new(target);
+    let result = match resolve_target(target, dns).await {
+        Ok(ip) => ping::run_ping_live(&ip, options, on_reply).await,
+        Err(e) => Err(e),
+    };
+    report.ping = Some(result.into());
+    report
+}
+
+/// Runs only the HTTP stage.
+pub async fn run_http(target: &str, family: Option<IpFamily>) -> Report {
+    let mut report = Report::new(target);
+    report.http = Some(http::check_http(target, family).await.into());
+    report
+}
+
+/// Runs only the traceroute stage, resolving the target first if it is a name.
+pub async fn run_trace(target: &str, dns: &DnsOptions, options: &TraceOptions) -> Report {
+    let mut report = Report::new(target);
+    let result = match resolve_target(target, dns).await {
+        Ok(ip) => trace::run_trace(&ip, options, dns).await,
+        Err(e) => Err(e),
+    };
+    report.trace = Some(result.into());
+    report
+}
+
+/// Runs a continuous MTR-style trace, calling `on_cycle` with the statistics
+/// after every round.
+pub async fn run_mtr(
+    target: &str,
+    dns: &DnsOptions,
+    options: &MtrOptions,
+    on_cycle: &mut (dyn FnMut(&MtrResult) + Send),
+) -> Report {
+    let mut report = Report::new(target);
+    let result = match resolve_target(target, dns).await {
+        Ok(ip) => mtr::run_mtr(&ip, options, dns, on_cycle).await,
+        Err(e) => Err(e),
+    };
+    report.mtr = Some(result.into());
+    report
+}
+
+/// Returns the target itself if it is an IP, otherwise its first resolved address.
+async fn resolve_target(target: &str, dns: &DnsOptions) -> Result<String, String> {
+    if let Ok(ip) = target.parse::<IpAddr>() {
+        return check_family(&ip, dns.family).map(|_| target.to_string());
+    }
+
+    let dns_result = dns::resolve_domain(target, dns).await?;
+    first_ip(&dns_result).ok_or_else(|| {
+        format!("No valid IPs found for {} ({})", target, address_statuses(&dns_result))
+    })
+}
+
+/// Returns the target itself if it is an IP, otherwise all of its resolved addresses.
+async fn resolve_all(target: &str, dns: &DnsOptions) -> Result<Vec<String>, String> {
+    if let Ok(ip) = target.parse::<IpAddr>() {
+        return check_family(&ip, dns.family).map(|_| vec![target.to_string()]);
+    }
+
+    let dns_result = dns::resolve_domain(target, dns).await?;
+    let ips = all_ips(&dns_result);
+    if ips.is_empty() {
+        return Err(format!("No valid IPs found for {} ({})", target, address_statuses(&dns_result)));
+    }
+    Ok(ips)
+}
+
+/// Rejects an IP target that contradicts -4 / -6.
+fn check_family(ip: &IpAddr, family: Option<IpFamily>) -> Result<(), String> {
+    match family {
+        Some(family) if !family.matches(ip) => Err(format!("{} is not an {} address", ip, family)),
+        _ => Ok(()),
+    }
+}
+
+/// Summarises why the A and AAAA queries came back empty, e.g. "A NXDOMAIN, AAAA NXDOMAIN"
+fn address_statuses(dns_result: &DnsResult) -> String {
+    dns_result
+        .answers
+        .iter()
+        .filter(|set| set.record_type == "A" || set.record_type == "AAAA")
+        .map(|set| format!("{} {}", set.record_type, set.status))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Picks the address the ping and trace stages should target.
+fn first_ip(dns_result: &DnsResult) -> Option<String> {
+    dns_result
+        .ipv4
+        .first()
+        .or_else(|| dns_result.ipv6.first())
+        .cloned()
+}
+
+/// Every IPv4 address, then every IPv6 address.
+fn all_ips(dns_result: &DnsResult) -> Vec<String> {
+    dns_result.ipv4.iter().chain(&dns_result.ipv6).cloned().collect()
+}
@@ -0,0 +1,233 @@
+// StaxPing — Unified Network Diagnostics
+// Copyright (c) 2026 StaxDash
+//
+// This source code is provided under the StaxPing Source‑Available License & EULA.
+// You may view, modify, and redistribute this code for personal or internal use.
+// Commercial use of any kind requires explicit written permission from StaxDash.
+//
+// Full license text available in LICENSE and EULA.md.
+
+//! Local stand-ins for the servers the unit tests talk to.
+
+use rustls::{Certificate, PrivateKey, ServerConfig};
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio_rustls::TlsAcceptor;
+use trust_dns_resolver::proto::op::{Message, MessageType, ResponseCode};
+use trust_dns_resolver::proto::rr::rdata::{A, AAAA, NS, PTR};
+use trust_dns_resolver::proto::rr::{RData, Record};
+use trust_dns_resolver::Name;
+
+/// The self-signed CA that issued the TLS stand-ins' certificate, which is
+/// valid for 127.0.0.1 and localhost
+pub const CA_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls/ca.pem");
+
+/// How often to look for a port again when the one tried is taken
+pub const BIND_ATTEMPTS: usize = 10;
+
+type Handler = dyn Fn(&Message) -> Message + Send + Sync;
+
+/// A local DNS server, stopped when dropped
+pub struct StubDns {
+    pub addr: SocketAddr,
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+}
+
+impl StubDns {
+    /// Answers over UDP and TCP on one port.
+    pub async fn start(handler: impl Fn(&Message) -> Message + Send + Sync + 'static) -> Self {
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        // The kernel picks a free UDP port; TCP has to take the same one
+        for _ in 0..BIND_ATTEMPTS {
+            match Self::serve_at("127.0.0.1:0".parse().unwrap(), handler.clone()).await {
+                Ok(stub) => return stub,
+                Err(e) if e.kind() == io::ErrorKind::AddrInUse => continue,
+                Err(e) => panic!("Failed to serve DNS on 127.0.0.1: {}", e),
+            }
+        }
+        panic!("No port free for both UDP and TCP after {} attempts", BIND_ATTEMPTS);
+    }
+
+    /// Answers over UDP and TCP on `addr`.
+    pub async fn start_at(addr: SocketAddr, handler: impl Fn(&Message) -> Message + Send + Sync + 'static) -> io::Result<Self> {
+        Self::serve_at(addr, Arc::new(handler)).await
+    }
+
+    async fn serve_at(addr: SocketAddr, handler: Arc<Handler>) -> io::Result<Self> {
+        let udp = UdpSocket::bind(addr).await?;
+        let addr = udp.local_addr()?;
+        let tcp = TcpListener::bind(addr).await?;
+
+        let udp_handler = handler.clone();
+        let udp_task = tokio::spawn(async move {
+            let mut buf = [0u8; 4096];
+            while let Ok((size, from)) = udp.recv_from(&mut buf).await {
+                if let Some(response) = respond(udp_handler.as_ref(), &buf[..size]) {
+                    let _ = udp.send_to(&response, from).await;
+                }
+            }
+        });
+
+        let tcp_task = tokio::spawn(async move {
+            while let Ok((stream, _)) = tcp.accept().await {
+                tokio::spawn(serve_stream(stream, handler.clone()));
+            }
+        });
+
+        Ok(StubDns { addr, tasks: vec![udp_task, tcp_task] })
+    }
+
+    /// Answers DNS-over-TLS.
+    pub async fn start_tls(handler: impl Fn(&Message) -> Message + Send + Sync + 'static) -> Self {
+        let handler: Arc<Handler> = Arc::new(handler);
+        let acceptor = tls_acceptor(Vec::new());
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = tcp.accept().await {
+                let (acceptor, handler) = (acceptor.clone(), handler.clone());
+                tokio::spawn(async move {
+                    if let Ok(stream) = acceptor.accept(stream).await {
+                        serve_stream(stream, handler).await;
+                    }
+                });
+            }
+        });
+
+        StubDns { addr, tasks: vec![task] }
+    }
+
+    /// Answers DNS-over-HTTPS: POSTs to /dns-query over HTTP/2.
+    pub async fn start_https(handler: impl Fn(&Message) -> Message + Send + Sync + 'static) -> Self {
+        let handler: Arc<Handler> = Arc::new(handler);
+        let acceptor = tls_acceptor(vec![b"h2".to_vec()]);
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = tcp.accept().await {
+                let (acceptor, handler) = (acceptor.clone(), handler.clone());
+                tokio::spawn(async move {
+                    let Ok(stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let Ok(mut connection) = h2::server::handshake(stream).await else {
+                        return;
+                    };
+                    while let Some(Ok((request, mut respond_to))) = connection.accept().await {
+                        let handler = handler.clone();
+                        tokio::spawn(async move {
+                            let (parts, mut body) = request.into_parts();
+                            let mut query = Vec::new();
+                            while let Some(Ok(chunk)) = body.data().await {
+                                let _ = body.flow_control().release_capacity(chunk.len());
+                                query.extend_from_slice(&chunk);
+                            }
+
+                            let answer = (parts.uri.path() == "/dns-query")
+                                .then(|| respond(handler.as_ref(), &query))
+                                .flatten();
+                            let status = if answer.is_some() { 200 } else { 400 };
+                            let answer = answer.unwrap_or_default();
+                            let response = http::Response::builder()
+                                .status(status)
+                                .header("content-type", "application/dns-message")
+                                .header("content-length", answer.len())
+                                .body(())
+                                .unwrap();
+                            if let Ok(mut stream) = respond_to.send_response(response, false) {
+                                let _ = stream.send_data(answer.into(), true);
+                            }
+                        });
+                    }
+                });
+            }
+        });
+
+        StubDns { addr, tasks: vec![task] }
+    }
+}
+
+impl Drop for StubDns {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Answers length-prefixed queries on a TCP or TLS stream until it closes.
+async fn serve_stream(mut stream: impl AsyncRead + AsyncWrite + Unpin, handler: Arc<Handler>) {
+    let mut len = [0u8; 2];
+    while stream.read_exact(&mut len).await.is_ok() {
+        let mut request = vec![0u8; u16::from_be_bytes(len) as usize];
+        if stream.read_exact(&mut request).await.is_err() {
+            break;
+        }
+        let Some(response) = respond(handler.as_ref(), &request) else {
+            break;
+        };
+        let _ = stream.write_all(&(response.len() as u16).to_be_bytes()).await;
+        let _ = stream.write_all(&response).await;
+    }
+}
+
+fn respond(handler: &Handler, request: &[u8]) -> Option<Vec<u8>> {
+    let request = Message::from_vec(request).ok()?;
+    let mut response = handler(&request);
+    response.set_id(request.id());
+    response.to_vec().ok()
+}
+
+fn tls_acceptor(alpn_protocols: Vec<Vec<u8>>) -> TlsAcceptor {
+    let pem = include_bytes!("../tests/fixtures/tls/server.pem");
+    let certs = rustls_pemfile::certs(&mut pem.as_slice()).unwrap();
+    let key = include_bytes!("../tests/fixtures/tls/server.key");
+    let key = rustls_pemfile::pkcs8_private_keys(&mut key.as_slice()).unwrap().remove(0);
+
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs.into_iter().map(Certificate).collect(), PrivateKey(key))
+        .unwrap();
+    config.alpn_protocols = alpn_protocols;
+    TlsAcceptor::from(Arc::new(config))
+}
+
+/// An empty response to `request` with the given code, echoing its question
+pub fn reply(request: &Message, code: ResponseCode) -> Message {
+    let mut response = Message::new();
+    response
+        .set_message_type(MessageType::Response)
+        .set_op_code(request.op_code())
+        .set_recursion_desired(request.recursion_desired())
+        .set_recursion_available(true)
+        .set_response_code(code)
+        .add_queries(request.queries().to_vec());
+    response
+}
+
+pub fn name(name: &str) -> Name {
+    Name::from_ascii(name).unwrap()
+}
+
+pub fn a(owner: &str, ip: Ipv4Addr) -> Record {
+    Record::from_rdata(name(owner), 60, RData::A(A(ip)))
+}
+
+pub fn aaaa(owner: &str, ip: Ipv6Addr) -> Record {
+    Record::from_rdata(name(owner), 60, RData::AAAA(AAAA(ip)))
+}
+
+pub fn ptr(owner: &str, target: &str) -> Record {
+    Record::from_rdata(name(owner), 60, RData::PTR(PTR(name(target))))
+}
+
+pub fn ns(owner: &str, server: &str) -> Record {
+    Record::from_rdata(name(owner), 60, RData::NS(NS(name(server))))
+}
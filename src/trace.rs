@@ -7,162 +7,1133 @@
 //
 // Full license text available in LICENSE and EULA.md.
 
+use clap::ValueEnum;
+use serde::Serialize;
 use tokio::process::Command;
 
-#[derive(Debug, Clone)]
+use crate::dns::{DnsOptions, PtrCache};
+
+#[derive(Serialize, Debug, Clone)]
 pub struct TraceHop {
     pub hop: u32,
     pub host: String,
+    /// First address that answered, or "*" if none did
     pub ip: String,
+    /// Round-trip times of the answered probes
     pub times_ms: Vec<f64>,
+    /// Every probe sent to this hop, in order
+    pub probes: Vec<TraceProbe>,
+    /// Every distinct address that answered at this TTL, in multipath mode
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub next_hops: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
+impl TraceHop {
+    pub fn from_probes(hop: u32, probes: Vec<TraceProbe>) -> Self {
+        let ip = probes
+            .iter()
+            .find_map(|probe| probe.ip.clone())
+            .unwrap_or_else(|| "*".into());
+
+        TraceHop {
+            hop,
+            host: ip.clone(),
+            ip,
+            times_ms: probes.iter().filter_map(|probe| probe.time_ms).collect(),
+            probes,
+            next_hops: Vec::new(),
+        }
+    }
+}
+
+/// One probe to a hop; a probe that timed out has neither address nor time
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TraceProbe {
+    pub ip: Option<String>,
+    pub time_ms: Option<f64>,
+}
+
+impl TraceProbe {
+    pub fn timed_out() -> Self {
+        TraceProbe { ip: None, time_ms: None }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
 pub struct TraceResult {
     pub hops: Vec<TraceHop>,
 }
 
-pub async fn run_trace(target: &str) -> Result<TraceResult, String> {
+/// What kind of packet the traceroute probes with
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceProtocol {
+    /// UDP datagrams to high ports, answered by port unreachable
+    #[default]
+    Udp,
+    /// ICMP echo requests, answered by echo replies
+    Icmp,
+    /// TCP SYNs to --trace-port, answered by SYN-ACK or RST
+    Tcp,
+}
+
+/// Settings for the traceroute stage
+#[derive(Debug, Clone, Copy)]
+pub struct TraceOptions {
+    /// Probes sent to each hop
+    pub probes: u32,
+    pub protocol: TraceProtocol,
+    /// Destination port of TCP probes
+    pub port: u16,
+    /// Keep every probe on one flow so load balancers send them down one path
+    pub paris: bool,
+    /// Spread each hop's probes over this many flows to find every path
+    pub multipath: Option<u32>,
+    /// Leave hops as bare addresses instead of looking up their names
+    pub numeric: bool,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        Self {
+            probes: 3,
+            protocol: TraceProtocol::Udp,
+            port: 80,
+            paris: false,
+            multipath: None,
+            numeric: false,
+        }
+    }
+}
+
+/// Traces the route to `target`, then names the hops through the resolver
+/// `dns` selects unless the options ask for numeric output.
+pub async fn run_trace(target: &str, options: &TraceOptions, dns: &DnsOptions) -> Result<TraceResult, String> {
+    let mut result = trace_route(target, options).await?;
+
+    if !options.numeric {
+        // Without a working resolver the hops simply keep their addresses
+        if let Ok(mut names) = PtrCache::new(dns).await {
+            name_hops(&mut result, &mut names).await;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Fills in `host` for every hop whose address has a PTR name. All hops
+/// are looked up at once, each given two seconds.
+pub async fn name_hops(result: &mut TraceResult, names: &mut PtrCache) {
+    let ips = result
+        .hops
+        .iter()
+        .filter_map(|hop| hop.ip.parse().ok())
+        .collect::<Vec<IpAddr>>();
+    names.resolve(ips, Duration::from_secs(2)).await;
+
+    for hop in &mut result.hops {
+        if let Some(name) = hop.ip.parse().ok().and_then(|ip| names.name(&ip)) {
+            hop.host = name.to_string();
+        }
+    }
+}
+
+async fn trace_route(target: &str, options: &TraceOptions) -> Result<TraceResult, String> {
     // Try system traceroute first
-    let traceroute_path = if std::path::Path::new("/usr/bin/traceroute").exists() {
-        "/usr/bin/traceroute"
-    } else if std::path::Path::new("/bin/traceroute").exists() {
-        "/bin/traceroute"
-    } else {
+    let Some(traceroute_path) = system_traceroute() else {
         // Fallback to pure Rust traceroute
-        return run_trace_fallback(target).await;
+        return run_trace_fallback(target, options).await;
     };
 
-    let output = Command::new(traceroute_path)
+    // Flow-stable probing is only implemented by the built-in tracer
+    if options.paris || options.multipath.is_some() {
+        return run_trace_fallback(target, options).await;
+    }
+
+    // Not every traceroute takes -T or -I, and TCP mode may need root; the
+    // built-in tracer covers both, and says why if it cannot run either
+    match run_system_traceroute(traceroute_path, target, options, MAX_HOPS).await {
+        Ok(result) => Ok(result),
+        Err(reason) => run_trace_fallback(target, options)
+            .await
+            .map_err(|e| format!("traceroute failed ({}); {}", reason, e)),
+    }
+}
+
+/// Where the system traceroute is installed, if it is
+pub fn system_traceroute() -> Option<&'static str> {
+    ["/usr/bin/traceroute", "/bin/traceroute"]
+        .into_iter()
+        .find(|path| std::path::Path::new(path).exists())
+}
+
+/// Runs the system traceroute once, up to `max_hops`. Fails with the first
+/// line of its stderr when it exits with an error or prints no hops.
+pub async fn run_system_traceroute(
+    path: &str,
+    target: &str,
+    options: &TraceOptions,
+    max_hops: u32,
+) -> Result<TraceResult, String> {
+    let mut command = Command::new(path);
+    match options.protocol {
+        TraceProtocol::Udp => &mut command,
+        TraceProtocol::Icmp => command.arg("-I"),
+        TraceProtocol::Tcp => command.arg("-T").arg("-p").arg(options.port.to_string()),
+    };
+
+    let output = command
         .arg("-n")
         .arg("-w")
         .arg("2")
+        .arg("-q")
+        .arg(options.probes.to_string())
+        .arg("-m")
+        .arg(max_hops.to_string())
         .arg(target)
+        .kill_on_drop(true)
         .output()
         .await
         .map_err(|e| format!("Failed to run traceroute: {}", e))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    let result = parse_traceroute(&String::from_utf8_lossy(&output.stdout))?;
+    if !output.status.success() || result.hops.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.lines().next().unwrap_or("no hops in its output").trim().to_string());
+    }
 
-    parse_traceroute(&stdout)
+    Ok(result)
 }
 
+/// Parses the output of the common traceroute implementations: Linux
+/// (iputils and the modern traceroute), GNU inetutils, BusyBox and BSD.
+///
+/// Each hop line starts with the hop number, then lists every probe as an
+/// RTT ("1.234 ms" or "1.234ms") or a "*" for a timeout. An address comes
+/// before the first RTT and again wherever a different router answered,
+/// either bare or as "name (address)". BSD-style tools print a router
+/// change on an indented line of its own, which continues the hop above.
+/// Annotations such as "!H" or "!N" are skipped.
 fn parse_traceroute(output: &str) -> Result<TraceResult, String> {
-    let mut hops = Vec::new();
+    let mut hops: Vec<TraceHop> = Vec::new();
 
     for line in output.lines() {
-        // Skip header line
-        if line.starts_with("traceroute") {
+        let mut tokens = line.split_whitespace().peekable();
+        let Some(first) = tokens.peek() else {
             continue;
-        }
-
-        // Example hop line:
-        // 1  192.168.1.1  1.234 ms  1.567 ms  1.890 ms
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() < 2 {
-            continue;
-        }
+        };
 
-        let hop_num = parts[0].parse::<u32>().unwrap_or(0);
-        let ip = parts[1].to_string();
+        // Continuation lines carry on from the last hop's responder
+        let (hop, mut probes, mut ip, mut host) = match first.parse::<u32>() {
+            Ok(hop) => {
+                tokens.next();
+                (hop, Vec::new(), None, None)
+            }
+            Err(_) if line.starts_with(char::is_whitespace) => match hops.pop() {
+                Some(last) => {
+                    let ip = last.probes.iter().rev().find_map(|probe| probe.ip.clone());
+                    let host = (last.host != last.ip).then(|| last.host.clone());
+                    (last.hop, last.probes, ip, host)
+                }
+                None => continue,
+            },
+            // Header and other non-hop lines
+            Err(_) => continue,
+        };
 
-        let mut times = Vec::new();
-        for part in parts.iter().skip(2) {
-            if part.ends_with("ms") {
-                let clean = part.replace("ms", "");
-                if let Ok(val) = clean.parse::<f64>() {
-                    times.push(val);
+        while let Some(token) = tokens.next() {
+            if token == "*" {
+                probes.push(TraceProbe::timed_out());
+            } else if let Some(time) = parse_rtt(token, tokens.peek().copied()) {
+                if tokens.peek() == Some(&"ms") {
+                    tokens.next();
                 }
+                probes.push(TraceProbe { ip: ip.clone(), time_ms: Some(time) });
+            } else if let Ok(address) = token.parse::<IpAddr>() {
+                ip = Some(address.to_string());
+            } else if let Some(address) = token
+                .strip_prefix('(')
+                .and_then(|token| token.strip_suffix(')'))
+                .and_then(|token| token.parse::<IpAddr>().ok())
+            {
+                ip = Some(address.to_string());
+            } else if !token.starts_with('!') && host.is_none() {
+                // A name, followed by its address in parentheses; the hop
+                // keeps the one of its first router
+                host = Some(token.to_string());
             }
         }
 
-        hops.push(TraceHop {
-            hop: hop_num,
-            host: ip.clone(),
-            ip,
-            times_ms: times,
-        });
+        let mut hop = TraceHop::from_probes(hop, probes);
+        if let Some(host) = host {
+            hop.host = host;
+        }
+        hops.push(hop);
     }
 
     Ok(TraceResult { hops })
 }
 
-use socket2::{Socket, Domain, Type, Protocol};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::time::Instant;
-use tokio::time::{timeout, Duration};
+/// Reads an RTT written as "1.234ms", or as "1.234" followed by "ms".
+fn parse_rtt(token: &str, next: Option<&str>) -> Option<f64> {
+    let number = match token.strip_suffix("ms") {
+        Some(number) => number,
+        None if next == Some("ms") => token,
+        None => return None,
+    };
+    number.parse().ok()
+}
 
-async fn run_trace_fallback(target: &str) -> Result<TraceResult, String> {
+use socket2::{Socket, SockRef, Domain, Type, Protocol};
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+/// How far the built-in tracer probes unless told otherwise
+pub const MAX_HOPS: u32 = 30;
+
+async fn run_trace_fallback(target: &str, options: &TraceOptions) -> Result<TraceResult, String> {
     let dest_ip: IpAddr = target
         .parse()
         .map_err(|_| format!("Invalid IP address for traceroute: {}", target))?;
 
-    let max_hops = 30;
-    let timeout_ms = 2000;
+    probe_path(dest_ip, options, MAX_HOPS).await
+}
+
+/// Runs the built-in tracer once, probing TTLs 1 to `max_hops`. The hops
+/// end early where the destination answered or the path was cut off.
+pub async fn probe_path(dest: IpAddr, options: &TraceOptions, max_hops: u32) -> Result<TraceResult, String> {
+    let options = *options;
+    tokio::task::spawn_blocking(move || Prober::new(dest, &options)?.trace(max_hops))
+        .await
+        .map_err(|e| format!("Traceroute task failed: {}", e))?
+}
 
-    let mut hops = Vec::new();
+/// Probes every TTL and gathers the replies to each, indexed by TTL - 1
+/// and then by probe, with `None` for the probes that timed out.
+fn probe_all_hops(
+    prober: &mut Prober,
+    options: &TraceOptions,
+    max_hops: u32,
+    received: &Receiver<Received>,
+) -> Result<Vec<Vec<Option<ProbeReply>>>, String> {
+    let timeout = Duration::from_millis(2000);
 
+    // Every TTL is probed up front and the replies share one deadline, so
+    // silent hops cost a single timeout in total rather than one each.
+    // Sending in TTL order keeps the near hops first in line when routers
+    // rate-limit their ICMP errors.
+    // In multipath mode each of a hop's probes takes its own flow.
+    let probes_per_hop = options.multipath.unwrap_or(options.probes);
+    let mut pending = HashMap::new();
+    // Held open until the end so the kernel cannot reuse their ports
+    let mut sockets = Vec::new();
     for ttl in 1..=max_hops {
-        let hop = trace_single_hop(dest_ip, ttl, timeout_ms).await?;
-
-        // detects blocked ICMP responses
-        if ttl == 1 && hop.ip == "*" {
-            return Err("Traceroute requires elevated privileges in this environment".into());
+        for index in 0..probes_per_hop as usize {
+            let sent = prober.send(ttl, index)?;
+            pending.insert(sent.key, Probe { ttl, index, sent: sent.at });
+            sockets.extend(sent.socket);
         }
+    }
 
-        hops.push(hop.clone());
+    let deadline = Instant::now() + timeout;
+    let mut replies: Vec<Vec<Option<ProbeReply>>> = vec![vec![None; probes_per_hop as usize]; max_hops as usize];
 
-        if hop.ip == dest_ip.to_string() {
+    loop {
+        // Done once the path has ended and every probe up to that hop is answered
+        let last_hop = replies.iter().position(|replies| {
+            replies.iter().flatten().any(|reply| reply.response != ProbeResponse::TimeExceeded)
+        });
+        if let Some(last_hop) = last_hop {
+            if pending.values().all(|probe: &Probe| probe.ttl as usize > last_hop + 1) {
+                break;
+            }
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let Ok(reply) = received.recv_timeout(remaining) else {
             break;
+        };
+        let Some(probe) = pending.remove(&reply.quote.key) else {
+            continue;
+        };
+
+        replies[probe.ttl as usize - 1][probe.index] = Some(ProbeReply {
+            ip: reply.from,
+            time_ms: reply.received.duration_since(probe.sent).as_secs_f64() * 1000.0,
+            response: reply.quote.response,
+        });
+    }
+
+    Ok(replies)
+}
+
+/// Reads replies off a raw socket until `done` is set, passing on the ones
+/// `parse` matches to a probe.
+fn receive_replies(
+    listener: &UdpSocket,
+    done: &AtomicBool,
+    replies: Sender<Received>,
+    parse: impl Fn(&[u8], IpAddr) -> Option<Quote>,
+) {
+    let mut buf = [0u8; 1500];
+
+    while !done.load(Ordering::Relaxed) {
+        // Times out regularly so `done` is noticed
+        let Ok((size, from)) = listener.recv_from(&mut buf) else {
+            continue;
+        };
+        let received = Instant::now();
+
+        // A raw socket sees every packet of its protocol on the host; skip
+        // the ones that are not about a probe of ours
+        if let Some(quote) = parse(&buf[..size], from.ip()) {
+            let _ = replies.send(Received { quote, from: from.ip(), received });
         }
     }
+}
 
-    Ok(TraceResult { hops })
+/// A reply as it came off a raw socket
+struct Received {
+    quote: Quote,
+    from: IpAddr,
+    received: Instant,
+}
+
+/// Identifies a probe: the local and destination ports of a UDP or TCP
+/// probe, or the identifier and sequence number of an echo request. On a
+/// stable flow the ports never change, so the second half is instead the
+/// UDP length or the low half of the TCP sequence number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ProbeKey(u16, u16);
+
+/// A probe just handed to the kernel
+struct SentProbe {
+    key: ProbeKey,
+    at: Instant,
+    /// Has to stay open until the reply arrives
+    socket: Option<Socket>,
+}
+
+/// A probe in flight
+struct Probe {
+    ttl: u32,
+    /// Position among the probes of its hop
+    index: usize,
+    sent: Instant,
+}
+
+/// A reply matched to one of our probes
+#[derive(Debug, Clone)]
+struct ProbeReply {
+    ip: IpAddr,
+    time_ms: f64,
+    response: ProbeResponse,
+}
+
+/// How a router or the destination answered a probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProbeResponse {
+    /// A router on the way dropped the probe when its TTL ran out
+    TimeExceeded,
+    /// The destination itself answered, so the probe arrived
+    Reached,
+    /// Some hop could not forward the probe (host, network or admin unreachable)
+    Unreachable,
+}
+
+/// What a reply says about the probe it belongs to
+struct Quote {
+    response: ProbeResponse,
+    key: ProbeKey,
 }
 
-async fn trace_single_hop(dest: IpAddr, ttl: u32, timeout_ms: u64) -> Result<TraceHop, String> {
-    let udp_socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_DEST_UNREACHABLE: u8 = 3;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_TIME_EXCEEDED: u8 = 11;
+const ICMP_PORT_UNREACHABLE: u8 = 3;
+
+const ICMPV6_DEST_UNREACHABLE: u8 = 1;
+const ICMPV6_TIME_EXCEEDED: u8 = 3;
+const ICMPV6_PORT_UNREACHABLE: u8 = 4;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+
+const TCP_SYN: u8 = 0x02;
+const TCP_SYN_ACK: u8 = 0x12;
+const TCP_RST: u8 = 0x04;
+
+/// Opens a raw socket of the family of `dest` that receives replies to our
+/// probes.
+fn open_listener(dest: IpAddr, v4_protocol: Protocol, v6_protocol: Protocol) -> Result<UdpSocket, String> {
+    let (domain, protocol) = match dest {
+        IpAddr::V4(_) => (Domain::IPV4, v4_protocol),
+        IpAddr::V6(_) => (Domain::IPV6, v6_protocol),
+    };
+
+    // Replies to probes only reach a raw socket, which needs privileges
+    let socket = Socket::new(domain, Type::RAW, Some(protocol))
+        .map_err(|_| "Traceroute requires elevated privileges in this environment".to_string())?;
+    socket.set_read_timeout(Some(Duration::from_millis(50)))
         .map_err(|e| format!("Socket error: {}", e))?;
+    // std's UdpSocket is a thin recvfrom wrapper, which is all a raw socket needs
+    Ok(UdpSocket::from(socket))
+}
+
+/// Sends probes of one protocol towards one destination and reads the
+/// replies. Its flows outlive a single trace, so repeated traces through
+/// the same prober keep every probe of a flow on one path.
+pub struct Prober {
+    dest: IpAddr,
+    options: TraceOptions,
+    /// Receives ICMP replies; echo requests go out through it too
+    icmp_socket: UdpSocket,
+    /// Receives SYN-ACKs and RSTs, which are not ICMP; hand-built SYNs of
+    /// stable flows go out through it
+    tcp_socket: Option<UdpSocket>,
+    /// Our address towards `dest`, which the TCP checksum covers
+    source: IpAddr,
+    echo_id: u16,
+    sequence: u16,
+    /// Stable flows to spread probes over; empty for classic probing,
+    /// which changes the flow with every probe
+    flows: Vec<Flow>,
+}
+
+/// The fields that fix a probe's path through load balancers
+struct Flow {
+    /// Source port of UDP and TCP probes, zero for ICMP
+    port: u16,
+    /// Keeps the port reserved while the flow is in use
+    socket: Option<Socket>,
+    /// Ones' complement sum every echo request of the flow is padded to,
+    /// so its ICMP checksum never changes
+    echo_sum: u16,
+}
+
+impl Prober {
+    pub fn new(dest: IpAddr, options: &TraceOptions) -> Result<Self, String> {
+        let tcp_socket = match options.protocol {
+            TraceProtocol::Tcp => Some(open_listener(dest, Protocol::TCP, Protocol::TCP)?),
+            _ => None,
+        };
+
+        let mut prober = Prober {
+            dest,
+            options: *options,
+            icmp_socket: open_listener(dest, Protocol::ICMPV4, Protocol::ICMPV6)?,
+            tcp_socket,
+            source: source_address(dest)?,
+            echo_id: std::process::id() as u16,
+            sequence: 0,
+            flows: Vec::new(),
+        };
 
-    udp_socket.set_ttl(ttl)
-        .map_err(|e| format!("Failed to set TTL: {}", e))?;
+        let flows = match options.multipath {
+            Some(flows) => flows,
+            None if options.paris => 1,
+            None => 0,
+        };
+        for flow in 0..flows {
+            let socket = match options.protocol {
+                TraceProtocol::Udp => Some(prober.probe_socket(Type::DGRAM, Protocol::UDP, 64)?),
+                TraceProtocol::Tcp => Some(prober.probe_socket(Type::STREAM, Protocol::TCP, 64)?),
+                TraceProtocol::Icmp => None,
+            };
+            prober.flows.push(Flow {
+                port: socket.as_ref().map(local_port).transpose()?.unwrap_or(0),
+                socket,
+                echo_sum: flow as u16,
+            });
+        }
+
+        Ok(prober)
+    }
+
+    /// Probes TTLs 1 to `max_hops` once. The hops end early where the
+    /// destination answered or the path was cut off.
+    pub fn trace(&mut self, max_hops: u32) -> Result<TraceResult, String> {
+        let socket_error = |e: std::io::Error| format!("Socket error: {}", e);
+        let icmp_listener = self.icmp_socket.try_clone().map_err(socket_error)?;
+        let tcp_listener = self.tcp_socket.as_ref().map(UdpSocket::try_clone).transpose().map_err(socket_error)?;
+        let dest = self.dest;
+        let options = self.options;
+
+        let done = AtomicBool::new(false);
+        let (sender, receiver) = mpsc::channel();
+
+        // Replies are read on their own threads and stamped as they land, so
+        // early hops are not timed against the rest of the send queue
+        let replies = std::thread::scope(|scope| {
+            let icmp_sender = sender.clone();
+            scope.spawn(|| {
+                receive_replies(&icmp_listener, &done, icmp_sender, |packet, from| {
+                    parse_icmp_reply(packet, from, dest, &options)
+                })
+            });
+            if let Some(tcp_listener) = &tcp_listener {
+                scope.spawn(|| {
+                    receive_replies(tcp_listener, &done, sender, |packet, from| {
+                        parse_tcp_reply(packet, from, dest, &options)
+                    })
+                });
+            }
+
+            let replies = probe_all_hops(self, &options, max_hops, &receiver);
+            done.store(true, Ordering::Relaxed);
+            replies
+        })?;
 
-    let local_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
-    udp_socket.bind(&local_addr.into())
+        let mut hops = Vec::new();
+
+        for (ttl, replies) in (1..).zip(replies) {
+            let probes = replies
+                .iter()
+                .map(|reply| match reply {
+                    Some(reply) => TraceProbe {
+                        ip: Some(reply.ip.to_string()),
+                        time_ms: Some(reply.time_ms),
+                    },
+                    None => TraceProbe::timed_out(),
+                })
+                .collect();
+            let mut hop = TraceHop::from_probes(ttl, probes);
+
+            if options.multipath.is_some() {
+                for address in hop.probes.iter().filter_map(|probe| probe.ip.clone()) {
+                    if !hop.next_hops.contains(&address) {
+                        hop.next_hops.push(address);
+                    }
+                }
+            }
+            hops.push(hop);
+
+            // Either the destination itself answered, or some hop could not
+            // forward the probe; both end the path here
+            if replies.iter().flatten().any(|reply| reply.response != ProbeResponse::TimeExceeded) {
+                break;
+            }
+        }
+
+        Ok(TraceResult { hops })
+    }
+
+    /// Sends one probe with the given TTL (hop limit). The probe number
+    /// picks the flow when there are several.
+    fn send(&mut self, ttl: u32, probe: usize) -> Result<SentProbe, String> {
+        self.sequence = self.sequence.wrapping_add(1);
+        if !self.flows.is_empty() {
+            return self.send_on_flow(ttl, probe % self.flows.len());
+        }
+
+        match self.options.protocol {
+            TraceProtocol::Udp => {
+                let dest_port = 33434 + ttl as u16;
+                let socket = self.probe_socket(Type::DGRAM, Protocol::UDP, ttl)?;
+                let local_port = local_port(&socket)?;
+                let at = Instant::now();
+                socket.send_to(&[0u8], &SocketAddr::new(self.dest, dest_port).into())
+                    .map_err(|e| format!("Send error: {}", e))?;
+                Ok(SentProbe { key: ProbeKey(local_port, dest_port), at, socket: Some(socket) })
+            }
+            TraceProtocol::Tcp => {
+                let socket = self.probe_socket(Type::STREAM, Protocol::TCP, ttl)?;
+                let local_port = local_port(&socket)?;
+                socket.set_nonblocking(true)
+                    .map_err(|e| format!("Socket error: {}", e))?;
+                // The handshake is left to the kernel; the SYN is all we need
+                let at = Instant::now();
+                match socket.connect(&SocketAddr::new(self.dest, self.options.port).into()) {
+                    Err(e) if !connect_in_progress(&e) => return Err(format!("Connect error: {}", e)),
+                    _ => {}
+                }
+                Ok(SentProbe { key: ProbeKey(local_port, self.options.port), at, socket: Some(socket) })
+            }
+            TraceProtocol::Icmp => {
+                let at = self.send_echo(ttl, 0)?;
+                Ok(SentProbe { key: ProbeKey(self.echo_id, self.sequence), at, socket: None })
+            }
+        }
+    }
+
+    /// Sends a probe whose addresses, protocol and ports (or ICMP checksum)
+    /// match every other probe of the flow.
+    fn send_on_flow(&mut self, ttl: u32, flow: usize) -> Result<SentProbe, String> {
+        let Flow { port, ref socket, echo_sum } = self.flows[flow];
+
+        match self.options.protocol {
+            TraceProtocol::Udp => {
+                // The length is not hashed by load balancers, so it tells the
+                // probes of a flow apart. It cycles so a long-lived flow never
+                // grows past the MTU; one trace sends far fewer probes than that
+                let socket = socket.as_ref().ok_or("Socket error: flow has no socket")?;
+                let length = self.sequence % UDP_FLOW_LENGTHS;
+                let payload = vec![0u8; length as usize];
+                set_ttl(&SockRef::from(socket), self.dest, ttl)?;
+                let at = Instant::now();
+                socket.send_to(&payload, &SocketAddr::new(self.dest, 33434).into())
+                    .map_err(|e| format!("Send error: {}", e))?;
+                Ok(SentProbe { key: ProbeKey(port, 8 + length), at, socket: None })
+            }
+            TraceProtocol::Tcp => {
+                let tcp_socket = self.tcp_socket.as_ref().ok_or("Socket error: no raw TCP socket")?;
+                let segment = self.syn_segment(port, self.sequence as u32);
+                set_ttl(&SockRef::from(tcp_socket), self.dest, ttl)?;
+                let at = Instant::now();
+                tcp_socket.send_to(&segment, SocketAddr::new(self.dest, 0))
+                    .map_err(|e| format!("Send error: {}", e))?;
+                Ok(SentProbe { key: ProbeKey(port, self.sequence), at, socket: None })
+            }
+            TraceProtocol::Icmp => {
+                // Pads the sequence number's contribution back to the flow's sum
+                let pad = ones_complement_add(echo_sum, !self.sequence);
+                let at = self.send_echo(ttl, pad)?;
+                Ok(SentProbe { key: ProbeKey(self.echo_id, self.sequence), at, socket: None })
+            }
+        }
+    }
+
+    /// Sends an echo request carrying the current sequence number and a
+    /// 16-bit pad word, returning when it went out.
+    fn send_echo(&self, ttl: u32, pad: u16) -> Result<Instant, String> {
+        let request_type = match self.dest {
+            IpAddr::V4(_) => ICMP_ECHO_REQUEST,
+            IpAddr::V6(_) => ICMPV6_ECHO_REQUEST,
+        };
+
+        let mut packet = [0u8; 16];
+        packet[0] = request_type;
+        packet[4..6].copy_from_slice(&self.echo_id.to_be_bytes());
+        packet[6..8].copy_from_slice(&self.sequence.to_be_bytes());
+        packet[8..10].copy_from_slice(&pad.to_be_bytes());
+        // The kernel fills in the ICMPv6 checksum itself
+        if self.dest.is_ipv4() {
+            let checksum = internet_checksum(&packet);
+            packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+        }
+
+        set_ttl(&SockRef::from(&self.icmp_socket), self.dest, ttl)?;
+        let at = Instant::now();
+        self.icmp_socket.send_to(&packet, SocketAddr::new(self.dest, 0))
+            .map_err(|e| format!("Send error: {}", e))?;
+        Ok(at)
+    }
+
+    /// A bare SYN from `source_port` to the probed port, checksummed over
+    /// the pseudo-header since raw sockets leave that to us.
+    fn syn_segment(&self, source_port: u16, sequence: u32) -> Vec<u8> {
+        let mut segment = vec![0u8; 20];
+        segment[0..2].copy_from_slice(&source_port.to_be_bytes());
+        segment[2..4].copy_from_slice(&self.options.port.to_be_bytes());
+        segment[4..8].copy_from_slice(&sequence.to_be_bytes());
+        // Header length of five 32-bit words, no options
+        segment[12] = 5 << 4;
+        segment[13] = TCP_SYN;
+        segment[14..16].copy_from_slice(&64240u16.to_be_bytes());
+
+        let mut pseudo_header = Vec::new();
+        match (self.source, self.dest) {
+            (IpAddr::V4(source), IpAddr::V4(dest)) => {
+                pseudo_header.extend(source.octets());
+                pseudo_header.extend(dest.octets());
+                pseudo_header.extend([0, 6]);
+                pseudo_header.extend((segment.len() as u16).to_be_bytes());
+            }
+            (source, dest) => {
+                pseudo_header.extend(ipv6_octets(source));
+                pseudo_header.extend(ipv6_octets(dest));
+                pseudo_header.extend((segment.len() as u32).to_be_bytes());
+                pseudo_header.extend([0, 0, 0, 6]);
+            }
+        }
+        pseudo_header.extend(&segment);
+
+        let checksum = internet_checksum(&pseudo_header);
+        segment[16..18].copy_from_slice(&checksum.to_be_bytes());
+        segment
+    }
+
+    /// A socket of the destination's family, bound to an ephemeral port,
+    /// whose packets expire after `ttl` hops.
+    fn probe_socket(&self, kind: Type, protocol: Protocol, ttl: u32) -> Result<Socket, String> {
+        let (domain, unspecified) = match self.dest {
+            IpAddr::V4(_) => (Domain::IPV4, IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            IpAddr::V6(_) => (Domain::IPV6, IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        };
+
+        let socket = Socket::new(domain, kind, Some(protocol))
+            .map_err(|e| format!("Socket error: {}", e))?;
+        set_ttl(&SockRef::from(&socket), self.dest, ttl)?;
+
+        let local_addr = SocketAddr::new(unspecified, 0);
+        socket.bind(&local_addr.into())
+            .map_err(|e| format!("Bind error: {}", e))?;
+        Ok(socket)
+    }
+}
+
+/// The local address the kernel would send from to reach `dest`
+fn source_address(dest: IpAddr) -> Result<IpAddr, String> {
+    let unspecified = match dest {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+
+    // Connecting a UDP socket sends nothing but picks the route
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0))
         .map_err(|e| format!("Bind error: {}", e))?;
+    socket.connect(SocketAddr::new(dest, 33434))
+        .map_err(|e| format!("No route to {}: {}", dest, e))?;
+    socket.local_addr()
+        .map(|addr| addr.ip())
+        .map_err(|e| format!("Socket error: {}", e))
+}
+
+fn ipv6_octets(address: IpAddr) -> [u8; 16] {
+    match address {
+        IpAddr::V4(address) => address.to_ipv6_mapped().octets(),
+        IpAddr::V6(address) => address.octets(),
+    }
+}
+
+/// Payload lengths UDP probes on a stable flow cycle through, more than
+/// the probes of one multipath trace (32 flows by 30 hops)
+const UDP_FLOW_LENGTHS: u16 = 1024;
+
+/// Whether a non-blocking connect failed only because it is under way:
+/// EINPROGRESS on Unix, WSAEWOULDBLOCK on Windows
+fn connect_in_progress(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    if error.raw_os_error() == Some(libc::EINPROGRESS) {
+        return true;
+    }
+    error.kind() == io::ErrorKind::WouldBlock
+}
 
-    let dest_addr = SocketAddr::new(dest, 33434 + ttl as u16);
+fn set_ttl(socket: &SockRef, dest: IpAddr, ttl: u32) -> Result<(), String> {
+    match dest {
+        IpAddr::V4(_) => socket.set_ttl(ttl),
+        IpAddr::V6(_) => socket.set_unicast_hops_v6(ttl),
+    }
+    .map_err(|e| format!("Failed to set TTL: {}", e))
+}
 
-    let start = Instant::now();
-    udp_socket.send_to(&[0u8], &dest_addr.into())
-        .map_err(|e| format!("Send error: {}", e))?;
+fn local_port(socket: &Socket) -> Result<u16, String> {
+    socket.local_addr()
+        .ok()
+        .and_then(|addr| addr.as_socket())
+        .map(|addr| addr.port())
+        .ok_or_else(|| "Bind error: no local port".to_string())
+}
 
-    // Allocate MaybeUninit buffer on heap
-    let mut buf = Box::new([std::mem::MaybeUninit::<u8>::uninit(); 512]);
+/// The Internet checksum: the complement of the ones' complement sum of
+/// the data as 16-bit words
+fn internet_checksum(data: &[u8]) -> u16 {
+    !data
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+        .fold(0, ones_complement_add)
+}
 
-    let socket_clone = udp_socket.try_clone()
-        .map_err(|e| format!("Socket clone error: {}", e))?;
+fn ones_complement_add(a: u16, b: u16) -> u16 {
+    let (sum, carry) = a.overflowing_add(b);
+    sum + carry as u16
+}
 
-    let recv_future = tokio::task::spawn_blocking(move || {
-        socket_clone.recv_from(&mut *buf)
-    });
+/// Matches an ICMP message from `from` to the probe it answers.
+///
+/// Echo replies answer an echo request directly. Errors quote the probe's
+/// IP header followed by at least the first 8 bytes of its payload, which
+/// hold the ports of a UDP or TCP probe and the identifier and sequence of
+/// an echo request. Raw ICMPv4 sockets deliver the IP header too; ICMPv6
+/// ones start at the ICMPv6 header.
+fn parse_icmp_reply(packet: &[u8], from: IpAddr, dest: IpAddr, options: &TraceOptions) -> Option<Quote> {
+    let protocol = options.protocol;
+    let (response, quoted_transport) = match dest {
+        IpAddr::V4(dest_v4) => {
+            let icmp = packet.get(ipv4_header_len(packet)?..)?;
+            let response = match (icmp[0], icmp[1]) {
+                (ICMP_ECHO_REPLY, _) if protocol == TraceProtocol::Icmp && from == dest => {
+                    return Some(Quote { response: ProbeResponse::Reached, key: echo_key(icmp)? });
+                }
+                (ICMP_TIME_EXCEEDED, _) => ProbeResponse::TimeExceeded,
+                (ICMP_DEST_UNREACHABLE, ICMP_PORT_UNREACHABLE) => ProbeResponse::Reached,
+                (ICMP_DEST_UNREACHABLE, _) if from == dest => ProbeResponse::Reached,
+                (ICMP_DEST_UNREACHABLE, _) => ProbeResponse::Unreachable,
+                _ => return None,
+            };
 
-    let result = timeout(Duration::from_millis(timeout_ms), recv_future).await;
+            // Any ICMP packet on the host reaches this socket, so the quote
+            // has to be long enough before it is read
+            let quoted_ip = icmp.get(8..)?;
+            let quoted_transport = quoted_ip.get(ipv4_header_len(quoted_ip)?..)?;
+            let quoted_dest = Ipv4Addr::new(quoted_ip[16], quoted_ip[17], quoted_ip[18], quoted_ip[19]);
+            if quoted_ip[9] != ip_protocol(protocol, dest) || quoted_dest != dest_v4 {
+                return None;
+            }
 
-    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+            (response, quoted_transport)
+        }
+        IpAddr::V6(dest_v6) => {
+            let response = match (*packet.first()?, *packet.get(1)?) {
+                (ICMPV6_ECHO_REPLY, _) if protocol == TraceProtocol::Icmp && from == dest => {
+                    return Some(Quote { response: ProbeResponse::Reached, key: echo_key(packet)? });
+                }
+                (ICMPV6_TIME_EXCEEDED, _) => ProbeResponse::TimeExceeded,
+                (ICMPV6_DEST_UNREACHABLE, ICMPV6_PORT_UNREACHABLE) => ProbeResponse::Reached,
+                (ICMPV6_DEST_UNREACHABLE, _) if from == dest => ProbeResponse::Reached,
+                (ICMPV6_DEST_UNREACHABLE, _) => ProbeResponse::Unreachable,
+                _ => return None,
+            };
+
+            let quoted_ip = packet.get(8..48)?;
+            let quoted_dest: [u8; 16] = quoted_ip[24..40].try_into().ok()?;
+            if quoted_ip[6] != ip_protocol(protocol, dest) || Ipv6Addr::from(quoted_dest) != dest_v6 {
+                return None;
+            }
 
-    let (ip, times_ms) = match result {
-        Ok(Ok(Ok((_size, addr)))) => {
-            let ip = addr.as_socket().unwrap().ip().to_string();
-            (ip, vec![elapsed])
+            (response, packet.get(48..)?)
         }
-        _ => ("*".into(), vec![elapsed]),
     };
 
-    Ok(TraceHop {
-        hop: ttl,
-        host: ip.clone(),
-        ip,
-        times_ms,
-    })
-}
\ No newline at end of file
+    let field = |at: usize| Some(u16::from_be_bytes([*quoted_transport.get(at)?, *quoted_transport.get(at + 1)?]));
+    let key = match (protocol, stable_flows(options)) {
+        (TraceProtocol::Icmp, _) => echo_key(quoted_transport)?,
+        (TraceProtocol::Udp | TraceProtocol::Tcp, false) => ProbeKey(field(0)?, field(2)?),
+        // UDP length, and the low half of the TCP sequence number
+        (TraceProtocol::Udp, true) => ProbeKey(field(0)?, field(4)?),
+        (TraceProtocol::Tcp, true) => ProbeKey(field(0)?, field(6)?),
+    };
+
+    Some(Quote { response, key })
+}
+
+/// Whether probes travel on stable flows rather than one flow per probe
+fn stable_flows(options: &TraceOptions) -> bool {
+    options.paris || options.multipath.is_some()
+}
+
+/// Matches a SYN-ACK or RST from the destination to the TCP probe it answers.
+fn parse_tcp_reply(packet: &[u8], from: IpAddr, dest: IpAddr, options: &TraceOptions) -> Option<Quote> {
+    if from != dest {
+        return None;
+    }
+
+    let tcp = match dest {
+        IpAddr::V4(_) => packet.get(ipv4_header_len(packet)?..)?,
+        IpAddr::V6(_) => packet,
+    };
+    let flags = *tcp.get(13)?;
+    if flags & TCP_SYN_ACK != TCP_SYN_ACK && flags & TCP_RST == 0 {
+        return None;
+    }
+
+    // The reply runs from the probed port back to our local one, and
+    // acknowledges the probe's sequence number plus one
+    let local_port = u16::from_be_bytes([tcp[2], tcp[3]]);
+    let key = if stable_flows(options) {
+        let ack = u32::from_be_bytes([tcp[8], tcp[9], tcp[10], tcp[11]]);
+        ProbeKey(local_port, ack.wrapping_sub(1) as u16)
+    } else {
+        ProbeKey(local_port, u16::from_be_bytes([tcp[0], tcp[1]]))
+    };
+
+    Some(Quote { response: ProbeResponse::Reached, key })
+}
+
+/// The identifier and sequence number of an ICMP echo message
+fn echo_key(icmp: &[u8]) -> Option<ProbeKey> {
+    Some(ProbeKey(
+        u16::from_be_bytes([*icmp.get(4)?, *icmp.get(5)?]),
+        u16::from_be_bytes([*icmp.get(6)?, *icmp.get(7)?]),
+    ))
+}
+
+/// The IP protocol number carried by probes, as quoted in ICMP errors
+fn ip_protocol(protocol: TraceProtocol, dest: IpAddr) -> u8 {
+    match (protocol, dest) {
+        (TraceProtocol::Udp, _) => 17,
+        (TraceProtocol::Tcp, _) => 6,
+        (TraceProtocol::Icmp, IpAddr::V4(_)) => 1,
+        (TraceProtocol::Icmp, IpAddr::V6(_)) => 58,
+    }
+}
+
+/// Length of an IPv4 header from its IHL field, if the packet holds one
+fn ipv4_header_len(packet: &[u8]) -> Option<usize> {
+    let len = (*packet.first()? & 0x0f) as usize * 4;
+    (len >= 20 && packet.len() >= len + 8).then_some(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEST: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 9));
+    const ROUTER: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+    /// A Time Exceeded from a router, quoting a UDP probe from port 40000 to 33435
+    fn time_exceeded_v4() -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 56, 0, 0, 0, 0, 64, 1, 0, 0, 192, 0, 2, 1, 192, 0, 2, 100];
+        packet.extend([ICMP_TIME_EXCEEDED, 0, 0, 0, 0, 0, 0, 0]);
+        packet.extend([0x45, 0, 0, 28, 0, 0, 0, 0, 1, 17, 0, 0, 192, 0, 2, 100, 203, 0, 113, 9]);
+        packet.extend([0x9c, 0x40, 0x81, 0x1b, 0, 8, 0, 0]);
+        packet
+    }
+
+    #[test]
+    fn icmp_reply_matches_quoted_probe() {
+        let quote = parse_icmp_reply(&time_exceeded_v4(), ROUTER, DEST, &TraceOptions::default()).unwrap();
+        assert_eq!(quote.response, ProbeResponse::TimeExceeded);
+        assert_eq!(quote.key, ProbeKey(40000, 33051));
+    }
+
+    #[test]
+    fn truncated_icmp_replies_are_ignored() {
+        let packet = time_exceeded_v4();
+        for len in 0..packet.len() - 4 {
+            assert!(parse_icmp_reply(&packet[..len], ROUTER, DEST, &TraceOptions::default()).is_none());
+        }
+
+        // An 8-byte Time Exceeded with nothing quoted
+        let mut packet = packet[..28].to_vec();
+        packet[3] = 28;
+        assert!(parse_icmp_reply(&packet, ROUTER, DEST, &TraceOptions::default()).is_none());
+    }
+
+    #[test]
+    fn truncated_icmpv6_replies_are_ignored() {
+        let dest = IpAddr::V6("2001:db8::9".parse().unwrap());
+        let router = IpAddr::V6("2001:db8::1".parse().unwrap());
+        let mut packet = vec![ICMPV6_TIME_EXCEEDED, 0, 0, 0, 0, 0, 0, 0];
+        packet.extend([0x60, 0, 0, 0, 0, 8, 17, 1]);
+        packet.extend("2001:db8::100".parse::<Ipv6Addr>().unwrap().octets());
+        packet.extend("2001:db8::9".parse::<Ipv6Addr>().unwrap().octets());
+        packet.extend([0x9c, 0x40, 0x81, 0x1b, 0, 8, 0, 0]);
+
+        let quote = parse_icmp_reply(&packet, router, dest, &TraceOptions::default()).unwrap();
+        assert_eq!(quote.key, ProbeKey(40000, 33051));
+        for len in 0..packet.len() - 4 {
+            assert!(parse_icmp_reply(&packet[..len], router, dest, &TraceOptions::default()).is_none());
+        }
+    }
+
+    #[test]
+    fn truncated_tcp_replies_are_ignored() {
+        let mut packet = vec![0x45, 0, 0, 40, 0, 0, 0, 0, 64, 6, 0, 0, 203, 0, 113, 9, 192, 0, 2, 100];
+        packet.extend([0, 80, 0x9c, 0x40, 0, 0, 0, 0, 0, 0, 0, 1, 0x50, TCP_SYN_ACK, 0, 0, 0, 0, 0, 0]);
+
+        let quote = parse_tcp_reply(&packet, DEST, DEST, &TraceOptions::default()).unwrap();
+        assert_eq!(quote.key, ProbeKey(40000, 80));
+        for len in 0..34 {
+            assert!(parse_tcp_reply(&packet[..len], DEST, DEST, &TraceOptions::default()).is_none());
+        }
+    }
+
+    const LINUX: &str = "\
+traceroute to example.com (93.184.216.34), 30 hops max, 60 byte packets
+ 1  192.168.1.1  0.512 ms  0.470 ms  0.455 ms
+ 2  * * *
+ 3  10.0.0.1  1.234 ms 10.0.0.2  1.456 ms *
+ 4  93.184.216.34  10.123 ms !H  10.245 ms !H  *
+";
+
+    const INETUTILS: &str = "\
+traceroute to 10.3.0.2 (10.3.0.2), 64 hops max
+  1   10.1.0.1  0.123ms  0.088ms  0.079ms 
+  2   *  *  * 
+  3   10.3.0.2  0.254ms  0.101ms  0.097ms 
+";
+
+    const BUSYBOX: &str = "\
+traceroute to 10.3.0.2 (10.3.0.2), 30 hops max, 38 byte packets
+ 1  gateway.lan (10.1.0.1)  0.157 ms  0.099 ms  0.090 ms
+ 2  *  *  *
+ 3  10.2.0.2 (10.2.0.2)  0.311 ms  10.4.0.2 (10.4.0.2)  0.287 ms  0.263 ms
+ 4  10.3.0.2 (10.3.0.2)  0.262 ms  0.181 ms  0.178 ms
+";
+
+    const BSD: &str = "\
+traceroute to example.com (93.184.216.34), 64 hops max, 52 byte packets
+ 1  router.lan (192.168.1.1)  1.123 ms  0.987 ms  0.954 ms
+ 2  a.example (10.0.0.1)  5.101 ms
+    b.example (10.0.0.2)  5.302 ms  5.403 ms
+ 3  2001:db8::1  3.000 ms  *  2.900 ms
+";
+
+    fn probe(ip: &str, time_ms: f64) -> TraceProbe {
+        TraceProbe { ip: Some(ip.into()), time_ms: Some(time_ms) }
+    }
+
+    #[test]
+    fn parses_linux_traceroute() {
+        let hops = parse_traceroute(LINUX).unwrap().hops;
+        assert_eq!(hops.len(), 4);
+        assert_eq!(hops[0].ip, "192.168.1.1");
+        assert_eq!(hops[0].times_ms, vec![0.512, 0.470, 0.455]);
+
+        assert_eq!(hops[1].hop, 2);
+        assert_eq!(hops[1].ip, "*");
+        assert_eq!(hops[1].probes.len(), 3);
+        assert!(hops[1].probes.iter().all(|probe| probe.time_ms.is_none()));
+
+        // A different router answered the second probe
+        assert_eq!(hops[2].probes[..2], [probe("10.0.0.1", 1.234), probe("10.0.0.2", 1.456)]);
+        assert_eq!(hops[2].probes[2], TraceProbe::timed_out());
+
+        // "!H" annotations are not probes
+        assert_eq!(hops[3].probes.len(), 3);
+        assert_eq!(hops[3].times_ms, vec![10.123, 10.245]);
+    }
+
+    #[test]
+    fn parses_inetutils_traceroute() {
+        let hops = parse_traceroute(INETUTILS).unwrap().hops;
+        assert_eq!(hops.len(), 3);
+        assert_eq!(hops[0].ip, "10.1.0.1");
+        assert_eq!(hops[0].times_ms, vec![0.123, 0.088, 0.079]);
+        assert_eq!(hops[1].probes, vec![TraceProbe::timed_out(); 3]);
+        assert_eq!(hops[2].probes[0], probe("10.3.0.2", 0.254));
+    }
+
+    #[test]
+    fn parses_busybox_traceroute() {
+        let hops = parse_traceroute(BUSYBOX).unwrap().hops;
+        assert_eq!(hops.len(), 4);
+        assert_eq!(hops[0].host, "gateway.lan");
+        assert_eq!(hops[0].ip, "10.1.0.1");
+        assert_eq!(hops[1].ip, "*");
+        assert_eq!(
+            hops[2].probes,
+            vec![probe("10.2.0.2", 0.311), probe("10.4.0.2", 0.287), probe("10.4.0.2", 0.263)]
+        );
+        assert_eq!(hops[2].host, hops[2].ip);
+        assert_eq!(hops[3].times_ms, vec![0.262, 0.181, 0.178]);
+    }
+
+    #[test]
+    fn parses_bsd_continuation_lines() {
+        let hops = parse_traceroute(BSD).unwrap().hops;
+        assert_eq!(hops.len(), 3);
+        assert_eq!(hops[0].host, "router.lan");
+
+        assert_eq!(hops[1].hop, 2);
+        assert_eq!(hops[1].host, "a.example");
+        assert_eq!(
+            hops[1].probes,
+            vec![probe("10.0.0.1", 5.101), probe("10.0.0.2", 5.302), probe("10.0.0.2", 5.403)]
+        );
+
+        assert_eq!(hops[2].ip, "2001:db8::1");
+        assert_eq!(hops[2].probes[1], TraceProbe::timed_out());
+        assert_eq!(hops[2].times_ms, vec![3.0, 2.9]);
+    }
+
+    #[test]
+    fn output_without_hops_is_empty() {
+        assert!(parse_traceroute("traceroute to 10.3.0.2 (10.3.0.2), 30 hops max\n").unwrap().hops.is_empty());
+    }
+}